@@ -1,11 +1,17 @@
 mod cli;
 
 use crate::cli::Cli;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use std::io::Write;
+use serde::Serialize;
+use std::io::BufReader;
 use std::os::unix::net::UnixStream;
-use tracing::error;
+use std::time::Duration;
+use tape::config::Config;
+use tape::device::HostInfo;
+use tape::proto::{self, ClientMessage, ServerMessage};
+use tape::{QueueEntry, Request, Status};
+use tracing::{error, warn};
 
 fn main() {
     if let Err(e) = run() {
@@ -18,12 +24,166 @@ fn run() -> Result<()> {
     tape::logger::init()?;
 
     let cli = Cli::parse();
-    let path = tape::socket_path()?;
+
+    let config = Config::load().unwrap_or_default();
+    let path = match config.socket() {
+        Some(path) => path.to_path_buf(),
+        None => tape::socket_path()?,
+    };
     let mut con = UnixStream::connect(&path)
         .with_context(|| format!("failed to connect to socket at {}", path.display()))?;
 
-    let req = serde_json::to_string(&cli.req)?;
-    con.write_all(req.as_bytes())?;
+    let mut reader = BufReader::new(con.try_clone()?);
+
+    let hello = ClientMessage::Hello {
+        version: proto::VERSION,
+    };
+    proto::send(&mut con, &hello)?;
+
+    match proto::recv(&mut reader)? {
+        Some(ServerMessage::Hello { .. }) => (),
+        Some(ServerMessage::Response { response, .. }) => {
+            response.into_result()?;
+        }
+        _ => bail!("handshake failed"),
+    }
+
+    let req = ClientMessage::Request {
+        id: 0,
+        request: cli.req.clone(),
+    };
+    proto::send(&mut con, &req)?;
+
+    // Responses without an ID report connection level failures.
+    let data = loop {
+        match proto::recv(&mut reader)?.context("connection closed")? {
+            ServerMessage::Response {
+                id: Some(0) | None,
+                response,
+            } => {
+                for warning in response.warnings() {
+                    warn!("{}", warning);
+                }
+
+                break response.into_result()?;
+            }
+            _ => continue,
+        }
+    };
+
+    match cli.req {
+        Request::Subscribe => {
+            while let Some(msg) = proto::recv::<_, ServerMessage>(&mut reader)? {
+                if let ServerMessage::Event(event) = msg {
+                    println!("{}", serde_json::to_string(&event)?);
+                }
+            }
+        }
+        Request::Status => print_status(serde_json::from_value(data)?),
+        Request::Queue => print_queue(serde_json::from_value(data)?),
+        Request::Devices => print_devices(serde_json::from_value(data)?),
+        _ if !data.is_null() => println!("{}", serde_json::to_string_pretty(&data)?),
+        _ => (),
+    }
 
     Ok(())
 }
+
+fn print_status(status: Status) {
+    println!("state: {}", label(&status.state));
+
+    if let (Some(index), Some(path)) = (status.index, &status.path) {
+        println!("track: {}", index);
+        println!("path: {}", path.display());
+
+        if let Some(metadata) = &status.metadata {
+            let tags = [
+                ("title", metadata.title()),
+                ("artist", metadata.artist()),
+                ("album", metadata.album()),
+            ];
+
+            for (key, value) in tags {
+                if let Some(value) = value {
+                    println!("{}: {}", key, value);
+                }
+            }
+        }
+
+        let position = status.position.map_or("--:--".into(), timestamp);
+
+        match status.duration {
+            Some(duration) => println!("position: {} / {}", position, timestamp(duration)),
+            None => println!("position: {}", position),
+        }
+    }
+
+    let mark = if status.mute { " (muted)" } else { "" };
+    println!("repeat-mode: {}", label(&status.repeat_mode));
+    println!("shuffle: {}", label(&status.shuffle));
+    println!("volume: {:.0}%{}", status.volume.level() * 100.0, mark);
+}
+
+fn print_queue(entries: Vec<QueueEntry>) {
+    let width = entries.len().saturating_sub(1).to_string().len();
+
+    for entry in entries {
+        let mark = if entry.current { '>' } else { ' ' };
+        let duration = entry.duration.map_or("--:--".into(), timestamp);
+        let name = match (entry.artist, entry.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title,
+            _ => entry.path.display().to_string(),
+        };
+
+        println!(
+            "{} {:>width$}  {:>8}  {}",
+            mark,
+            entry.index,
+            duration,
+            name,
+            width = width
+        );
+    }
+}
+
+fn timestamp(t: Duration) -> String {
+    let secs = t.as_secs();
+
+    match secs / 3600 {
+        0 => format!("{}:{:02}", secs / 60, secs % 60),
+        h => format!("{}:{:02}:{:02}", h, secs / 60 % 60, secs % 60),
+    }
+}
+
+fn label<T>(value: &T) -> String
+where
+    T: Serialize,
+{
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn print_devices(hosts: Vec<HostInfo>) {
+    for host in hosts {
+        let mark = if host.is_default() { " (default)" } else { "" };
+        println!("{}{}", host.name(), mark);
+
+        for device in host.devices() {
+            let mark = if device.is_default() { '*' } else { ' ' };
+            println!("  {} {}", mark, device.name());
+
+            for config in device.configs() {
+                println!(
+                    "      {} ch, {}-{} Hz, {}",
+                    config.channels(),
+                    config.min_rate(),
+                    config.max_rate(),
+                    config.format()
+                );
+            }
+        }
+    }
+}
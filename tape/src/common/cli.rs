@@ -1,10 +1,21 @@
 use clap::error::ErrorKind;
 use clap::error::Result;
 use clap::Error;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 pub fn expand_path(path: &str) -> std::io::Result<PathBuf> {
-    std::fs::canonicalize(path)
+    match std::fs::canonicalize(path) {
+        // The daemon looks up relative paths missing here in its library directories.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && Path::new(path).is_relative() => {
+            Ok(path.into())
+        }
+        result => result,
+    }
+}
+
+pub fn absolute_path(path: &str) -> std::io::Result<PathBuf> {
+    std::path::absolute(path)
 }
 
 pub fn parse_prop(s: &str) -> Result<(String, String)> {
@@ -16,3 +27,58 @@ pub fn parse_prop(s: &str) -> Result<(String, String)> {
         Err(Error::new(ErrorKind::TooFewValues))
     }
 }
+
+pub fn parse_range(s: &str) -> Result<Range<usize>> {
+    let invalid = || Error::new(ErrorKind::InvalidValue);
+    let index = |s: &str| s.trim().parse::<usize>().map_err(|_| invalid());
+
+    let range = match s.split_once("..") {
+        Some((start, end)) => match end.strip_prefix('=') {
+            Some(end) => index(start)?..index(end)?.checked_add(1).ok_or_else(invalid)?,
+            None if end.trim().is_empty() => index(start)?..usize::MAX,
+            None => index(start)?..index(end)?,
+        },
+        None => {
+            let n = index(s)?;
+            n..n.saturating_add(1)
+        }
+    };
+
+    if range.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(parse_range("3").unwrap(), 3..4);
+        assert_eq!(parse_range("1..4").unwrap(), 1..4);
+        assert_eq!(parse_range("1..=4").unwrap(), 1..5);
+        assert_eq!(parse_range(" 2 .. ").unwrap(), 2..usize::MAX);
+    }
+
+    #[test]
+    fn parse_invalid_ranges() {
+        for s in ["", "a", "-1", "..3", "3..3", "4..2", "1..=a", "1...3"] {
+            assert!(parse_range(s).is_err(), "{s:?}");
+        }
+
+        assert!(parse_range(&format!("0..={}", usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn parse_props() {
+        assert_eq!(
+            parse_prop("volume=50%").unwrap(),
+            ("volume".into(), "50%".into())
+        );
+        assert_eq!(parse_prop("a=b=c").unwrap(), ("a".into(), "b=c".into()));
+        assert!(parse_prop("volume").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use tracing_subscriber::filter::LevelFilter;
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    #[serde(deserialize_with = "parse_level")]
+    log_level: Option<LevelFilter>,
+    socket: Option<PathBuf>,
+    library: Vec<PathBuf>,
+    playback: toml::Table,
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf> {
+        let mut path = dirs::config_dir().context("failed to determine config directory")?;
+        path.push("tape");
+        path.push("config.toml");
+        Ok(path)
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        let s = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("{}: failed to read config", path.display()))
+            }
+        };
+
+        toml::from_str(&s).with_context(|| format!("{}: invalid config", path.display()))
+    }
+
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        self.log_level
+    }
+
+    pub fn socket(&self) -> Option<&Path> {
+        self.socket.as_deref()
+    }
+
+    pub fn library(&self) -> &[PathBuf] {
+        &self.library
+    }
+
+    pub fn playback(&self) -> Vec<(String, String)> {
+        self.playback
+            .iter()
+            .map(|(key, value)| {
+                let value = match (key.as_str(), value) {
+                    // A bare number is a level, as in `volume = 0.5`.
+                    ("volume", toml::Value::Float(level)) => format!("{}%", level * 100.0),
+                    ("volume", toml::Value::Integer(level)) => format!("{}%", level * 100),
+                    (_, toml::Value::String(s)) => s.clone(),
+                    (_, value) => value.to_string(),
+                };
+
+                (key.clone(), value)
+            })
+            .collect()
+    }
+}
+
+fn parse_level<'de, D>(de: D) -> Result<Option<LevelFilter>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(de)?;
+    let level = s
+        .parse()
+        .map_err(|_| D::Error::custom(format!("{}: invalid log level", s)))?;
+
+    Ok(Some(level))
+}
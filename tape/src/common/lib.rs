@@ -1,5 +1,7 @@
 mod cli;
+pub mod config;
 pub mod logger;
+pub mod proto;
 
 pub use tape_core as core;
 pub use tape_core::*;
@@ -7,7 +9,17 @@ pub use tape_core::*;
 use anyhow::{Context, Result};
 use clap::{Subcommand, ValueHint};
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use std::path::PathBuf;
+use std::time::Duration;
+use tape_core::engine::PlaybackState;
+use tape_core::export::SampleFormat;
+use tape_core::factory::RepeatMode;
+use tape_core::gain::Volume;
+use tape_core::io::SeekFrom;
+use tape_core::meta::Metadata;
+use tape_core::shuffle::Shuffle;
+use thiserror::Error;
 
 pub fn runtime_dir() -> Result<PathBuf> {
     let mut path = dirs::runtime_dir().context("failed to determine runtime directory")?;
@@ -22,33 +34,69 @@ pub fn socket_path() -> Result<PathBuf> {
     Ok(path)
 }
 
-#[derive(Subcommand, Serialize, Deserialize)]
+#[derive(Clone, Subcommand, Serialize, Deserialize)]
 pub enum Request {
     /// Add track(s) to queue
     Add {
         /// Paths of the track(s) to add
         #[arg(value_hint = ValueHint::AnyPath, value_parser=cli::expand_path)]
         paths: Vec<PathBuf>,
+        /// Index to insert the track(s) at, defaults to the end of the queue
+        #[arg(value_name = "POSITION", long = "at", conflicts_with = "next")]
+        at: Option<usize>,
+        /// Insert the track(s) right after the currently playing track
+        #[arg(short = 'n', long = "next")]
+        next: bool,
     },
     /// Remove track(s) from queue
     Remove {
-        /// Indice(s) of the track(s) to remove
-        ids: Vec<usize>,
+        /// Indice(s) or ranges (N, A..B, A..=B, A..) of the track(s) to remove
+        #[arg(value_name = "RANGE", value_parser = cli::parse_range)]
+        ids: Vec<Range<usize>>,
     },
+    /// Move track(s) to another position in queue
+    Move {
+        /// Index or range (N, A..B, A..=B, A..) of the track(s) to move
+        #[arg(value_name = "RANGE", value_parser = cli::parse_range)]
+        from: Range<usize>,
+        /// Index the track(s) should end up at
+        #[arg(value_name = "POSITION")]
+        to: usize,
+    },
+    /// Remove all tracks from queue
+    Clear,
     /// Configure playback at runtime
     Config {
         /// Key-value property pairs separated by the '=' sign
         ///
         /// Possible properties are:
         /// repeat-mode=[disabled, track, playlist]     Should player repeat track(s) and how
+        /// shuffle=[off, track, album]                 Should player shuffle the queue and how
+        /// resample-quality=[low, medium, high]        Quality of sample rate conversion
+        /// volume=[N%, +N%, -N%, NdB, +NdB, -NdB]      Playback volume, absolute or relative to current
+        /// mute=[true, false, toggle]                  Should player silence playback
+        /// replaygain=[off, track, album]              Should player normalize loudness and how
+        /// replaygain-preamp=[DECIBELS]                Gain added to the normalized loudness
+        /// crossfade=[SECONDS]                         Overlap between tracks, 0 disables crossfading
+        /// crossfade-curve=[linear, equal-power]       Shape of the crossfade
+        /// device=[NAME, HOST:NAME, default]           Output device to play through
         #[arg(value_name = "PROPERTY", short = 'p', long = "property", value_parser = cli::parse_prop, verbatim_doc_comment)]
         props: Vec<(String, String)>,
     },
     /// Seek track currently playing
     Seek {
-        /// Timestamp in seconds passed since the beginning of a track
-        #[arg(value_name = "TIMESTAMP")]
-        t: u64,
+        /// Position to seek to
+        ///
+        /// Possible formats are:
+        /// SECONDS, MM:SS, HH:MM:SS    Absolute position, fractional seconds allowed
+        /// +SECONDS, -SECONDS          Position relative to the current one
+        /// N%                          Position as a percentage of the track duration
+        #[arg(
+            value_name = "POSITION",
+            allow_hyphen_values = true,
+            verbatim_doc_comment
+        )]
+        pos: SeekFrom,
     },
     /// Select track from current playlist
     Jump {
@@ -59,8 +107,141 @@ pub enum Request {
         #[arg(short = 'r', long = "relative")]
         relative: bool,
     },
+    /// Show playback status
+    Status,
+    /// List tracks in queue
+    Queue,
+    /// Print playback events as they happen
+    #[command(name = "watch")]
+    Subscribe,
+    /// List output devices
+    Devices,
+    /// Render queue to an audio file
+    Export {
+        /// Path of the file to write, its extension selects the container (wav or flac)
+        #[arg(value_hint = ValueHint::FilePath, value_parser = cli::absolute_path)]
+        path: PathBuf,
+        /// Sample format [s16, s24, s32, f32], flac supports s16 and s24 only
+        #[arg(short = 'f', long = "format", default_value = "s16")]
+        format: SampleFormat,
+        /// Sample rate in Hz
+        #[arg(short = 'r', long = "rate", default_value_t = 44100)]
+        rate: u32,
+        /// Number of channels
+        #[arg(short = 'c', long = "channels", default_value_t = 2)]
+        channels: usize,
+        /// Stop after the given number of seconds, required when a repeat mode is set
+        #[arg(short = 'd', long = "duration", value_name = "SECONDS")]
+        duration: Option<f64>,
+    },
+    /// Reload the daemon configuration file
+    Reload,
     /// Continue playback
     Play,
     /// Stop playback
     Pause,
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Response {
+    Success {
+        #[serde(default)]
+        data: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
+    Failure {
+        error: ResponseError,
+    },
+}
+
+impl Response {
+    pub fn warnings(&self) -> &[String] {
+        match self {
+            Self::Success { warnings, .. } => warnings,
+            Self::Failure { .. } => &[],
+        }
+    }
+
+    pub fn into_result(self) -> Result<serde_json::Value, ResponseError> {
+        match self {
+            Self::Success { data, .. } => Ok(data),
+            Self::Failure { error } => Err(error),
+        }
+    }
+}
+
+impl From<Result<serde_json::Value, ResponseError>> for Response {
+    fn from(result: Result<serde_json::Value, ResponseError>) -> Self {
+        match result {
+            Ok(data) => Self::Success {
+                data,
+                warnings: Vec::new(),
+            },
+            Err(error) => Self::Failure { error },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Status {
+    pub state: PlaybackState,
+    pub index: Option<usize>,
+    pub path: Option<PathBuf>,
+    pub position: Option<Duration>,
+    pub duration: Option<Duration>,
+    pub repeat_mode: RepeatMode,
+    pub shuffle: Shuffle,
+    pub volume: Volume,
+    pub mute: bool,
+    pub metadata: Option<Metadata>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct QueueEntry {
+    pub index: usize,
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration: Option<Duration>,
+    pub current: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    Protocol,
+    InvalidRequest,
+    UnknownProperty,
+    InvalidValue,
+    NotFound,
+    Probe,
+    Seek,
+    Device,
+    Export,
+    Config,
+    Busy,
+    Internal,
+}
+
+#[derive(Error, Debug, Serialize, Deserialize)]
+#[error("{message}")]
+pub struct ResponseError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ResponseError {
+    pub fn new<S>(kind: ErrorKind, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
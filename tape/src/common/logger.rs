@@ -1,10 +1,12 @@
 use anyhow::Result;
 use tracing::Subscriber;
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::LookupSpan;
-use tracing_subscriber::Layer;
+use tracing_subscriber::{reload, Layer, Registry};
 
 pub type Logger<S> = Box<dyn 'static + Layer<S> + Send + Sync>;
+pub type LevelHandle = reload::Handle<LevelFilter, Registry>;
 
 pub fn stderr<S>() -> Logger<S>
 where
@@ -28,3 +30,11 @@ pub fn init() -> Result<()> {
 
     Ok(())
 }
+
+pub fn init_reloadable() -> Result<LevelHandle> {
+    let (filter, handle) = reload::Layer::new(LevelFilter::TRACE);
+    let subscriber = tracing_subscriber::registry().with(filter).with(stderr());
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    Ok(handle)
+}
@@ -0,0 +1,70 @@
+use crate::{Request, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Read, Write};
+use tape_core::event::Event;
+use thiserror::Error;
+
+pub const VERSION: u32 = 1;
+pub const MIN_VERSION: u32 = 1;
+
+const MAX_FRAME: u64 = 1 << 20;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientMessage {
+    Hello { version: u32 },
+    Request { id: u64, request: Request },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ServerMessage {
+    Hello { version: u32 },
+    Response { id: Option<u64>, response: Response },
+    Event(Event),
+}
+
+pub fn send<W, T>(w: &mut W, msg: &T) -> Result<(), ProtoError>
+where
+    W: Write,
+    T: Serialize,
+{
+    let mut frame = serde_json::to_vec(msg).map_err(ProtoError::Malformed)?;
+    frame.push(b'\n');
+    w.write_all(&frame)?;
+
+    Ok(())
+}
+
+pub fn recv<R, T>(r: &mut R) -> Result<Option<T>, ProtoError>
+where
+    R: BufRead,
+    T: DeserializeOwned,
+{
+    let mut frame = Vec::new();
+    r.by_ref().take(MAX_FRAME).read_until(b'\n', &mut frame)?;
+
+    match frame.last() {
+        None => return Ok(None),
+        Some(b'\n') => (),
+        Some(_) if frame.len() as u64 == MAX_FRAME => return Err(ProtoError::TooLong),
+        Some(_) => return Err(ProtoError::Truncated),
+    }
+
+    serde_json::from_slice(&frame)
+        .map(Some)
+        .map_err(ProtoError::Malformed)
+}
+
+#[derive(Error, Debug)]
+pub enum ProtoError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("malformed message: {0}")]
+    Malformed(serde_json::Error),
+    #[error("message too long")]
+    TooLong,
+    #[error("connection closed mid-message")]
+    Truncated,
+}
@@ -0,0 +1,18 @@
+use clap::{ColorChoice, Parser, ValueEnum};
+
+#[derive(Parser)]
+#[command(color = ColorChoice::Never)]
+#[clap(about = "A terminal audio player daemon")]
+pub struct Cli {
+    /// Output to play through
+    #[arg(short = 'o', long = "output", value_enum, default_value_t = OutputKind::Device)]
+    pub output: OutputKind,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputKind {
+    /// Default audio device
+    Device,
+    /// Discard audio in real time without a sound card
+    Null,
+}
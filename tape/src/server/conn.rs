@@ -0,0 +1,245 @@
+use crate::Message;
+use anyhow::{anyhow, Context, Result};
+use parking_lot::Mutex;
+use std::io::BufReader;
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tape::proto::{self, ClientMessage, ProtoError, ServerMessage};
+use tape::{ErrorKind, Request, Response, ResponseError};
+use tracing::warn;
+
+const MAX_CONNECTIONS: usize = 64;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub type Subscribers = Arc<Mutex<Vec<Weak<Sender<ServerMessage>>>>>;
+
+pub struct Command {
+    pub req: Request,
+    pub reply: Sender<Response>,
+}
+
+pub fn listen(sock: UnixListener, commands: Sender<Message>, subscribers: Subscribers) {
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for con in sock.incoming() {
+        let mut con = match con {
+            Ok(con) => con,
+            Err(e) => {
+                warn!("failed to accept incoming connection: {}", e);
+                continue;
+            }
+        };
+
+        let slot = match Slot::acquire(&connections) {
+            Some(slot) => slot,
+            None => {
+                let msg = ServerMessage::Response {
+                    id: None,
+                    response: Err(ResponseError::new(ErrorKind::Busy, "too many connections"))
+                        .into(),
+                };
+
+                if let Err(e) = proto::send(&mut con, &msg) {
+                    warn!("failed to send message: {}", e);
+                }
+
+                continue;
+            }
+        };
+
+        let commands = commands.clone();
+        let subscribers = subscribers.clone();
+        let result = std::thread::Builder::new()
+            .name("client".into())
+            .spawn(move || {
+                let _slot = slot;
+
+                if let Err(e) = serve(con, &commands, &subscribers) {
+                    warn!("{:#}", e);
+                }
+            });
+
+        if let Err(e) = result {
+            warn!("failed to spawn client thread: {}", e);
+        }
+    }
+}
+
+fn serve(con: UnixStream, commands: &Sender<Message>, subscribers: &Subscribers) -> Result<()> {
+    con.set_read_timeout(Some(READ_TIMEOUT))
+        .and_then(|_| con.set_write_timeout(Some(WRITE_TIMEOUT)))
+        .context("failed to configure connection")?;
+
+    let mut reader = BufReader::new(con.try_clone()?);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let writer = std::thread::Builder::new()
+        .name("writer".into())
+        .spawn(move || write_messages(con, rx))
+        .context("failed to spawn writer thread")?;
+    let tx = Arc::new(tx);
+
+    if handshake(&mut reader, &tx) {
+        read_requests(&mut reader, &tx, commands, subscribers);
+    }
+
+    // Messages still queued are flushed before the connection closes.
+    drop(tx);
+    writer
+        .join()
+        .map_err(|_| anyhow!("writer thread panicked"))?
+}
+
+fn handshake(reader: &mut BufReader<UnixStream>, tx: &Sender<ServerMessage>) -> bool {
+    let message = match proto::recv(reader) {
+        Ok(Some(ClientMessage::Hello { version })) if version >= proto::MIN_VERSION => {
+            let version = std::cmp::min(version, proto::VERSION);
+            return tx.send(ServerMessage::Hello { version }).is_ok();
+        }
+        Ok(Some(ClientMessage::Hello { version })) => {
+            format!("{}: unsupported protocol version", version)
+        }
+        Ok(Some(_)) => "expected handshake".into(),
+        Ok(None) => return false,
+        Err(e) => describe(e),
+    };
+
+    fail(tx, None, ResponseError::new(ErrorKind::Protocol, message));
+
+    false
+}
+
+fn read_requests(
+    reader: &mut BufReader<UnixStream>,
+    tx: &Arc<Sender<ServerMessage>>,
+    commands: &Sender<Message>,
+    subscribers: &Subscribers,
+) {
+    if let Err(e) = reader.get_ref().set_read_timeout(Some(IDLE_TIMEOUT)) {
+        warn!("failed to configure connection: {}", e);
+        return;
+    }
+
+    loop {
+        let (id, req) = match proto::recv(reader) {
+            Ok(Some(ClientMessage::Request { id, request })) => (id, request),
+            Ok(Some(ClientMessage::Hello { .. })) => {
+                let e = ResponseError::new(ErrorKind::Protocol, "handshake already done");
+                fail(tx, None, e);
+                continue;
+            }
+            Ok(None) => return,
+            // The offending line is consumed, so the next one can still be read.
+            Err(e @ ProtoError::Malformed(_)) => {
+                fail(
+                    tx,
+                    None,
+                    ResponseError::new(ErrorKind::InvalidRequest, e.to_string()),
+                );
+                continue;
+            }
+            Err(e) => {
+                fail(
+                    tx,
+                    None,
+                    ResponseError::new(ErrorKind::Protocol, describe(e)),
+                );
+                return;
+            }
+        };
+
+        let response = match req {
+            Request::Subscribe => {
+                // Subscribers stay connected for as long as they want events.
+                if let Err(e) = reader.get_ref().set_read_timeout(None) {
+                    warn!("failed to configure connection: {}", e);
+                }
+
+                subscribers.lock().push(Arc::downgrade(tx));
+                Ok(serde_json::Value::Null).into()
+            }
+            req => execute(req, commands),
+        };
+
+        let msg = ServerMessage::Response {
+            id: Some(id),
+            response,
+        };
+
+        if tx.send(msg).is_err() {
+            return;
+        }
+    }
+}
+
+fn execute(req: Request, commands: &Sender<Message>) -> Response {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let shutdown = || ResponseError::new(ErrorKind::Internal, "player is shutting down");
+
+    if commands
+        .send(Message::Command(Command { req, reply: tx }))
+        .is_err()
+    {
+        return Err(shutdown()).into();
+    }
+
+    rx.recv().unwrap_or_else(|_| Err(shutdown()).into())
+}
+
+fn write_messages(mut con: UnixStream, rx: Receiver<ServerMessage>) -> Result<()> {
+    for msg in rx {
+        // A client that hung up or stopped reading is dropped. Shutting the socket down also
+        // wakes the reader, which may be blocked without a timeout on a subscribed connection.
+        if let Err(e) = proto::send(&mut con, &msg) {
+            let _ = con.shutdown(Shutdown::Both);
+            return Err(e).context("failed to send message");
+        }
+    }
+
+    Ok(())
+}
+
+fn fail(tx: &Sender<ServerMessage>, id: Option<u64>, e: ResponseError) {
+    let _ = tx.send(ServerMessage::Response {
+        id,
+        response: Err(e).into(),
+    });
+}
+
+fn describe(e: ProtoError) -> String {
+    match e {
+        ProtoError::Io(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            ) =>
+        {
+            "timed out waiting for message".into()
+        }
+        e => e.to_string(),
+    }
+}
+
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn acquire(connections: &Arc<AtomicUsize>) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < MAX_CONNECTIONS).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(connections.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
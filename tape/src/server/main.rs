@@ -1,13 +1,37 @@
-use anyhow::{bail, Context, Result};
+mod cli;
+mod conn;
+mod session;
+
+use crate::cli::{Cli, OutputKind};
+use crate::conn::{Command, Subscribers};
+use crate::session::{Cursor, Queue, Session, Snapshot};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
 use std::fs::{DirEntry, File};
-use std::io::Read;
-use std::os::unix::net::{UnixListener, UnixStream};
+use std::io::BufWriter;
+use std::os::unix::net::UnixListener;
 use std::path::{Path, PathBuf};
-use tape::engine::PlaybackState;
-use tape::factory::{FactoryState, TranslateBehavior};
-use tape::Request;
-use tape::{Engine, Factory, Sound};
-use tracing::{debug, error, warn};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tape::buf::Layout;
+use tape::config::Config;
+use tape::engine::{EngineError, PlaybackState};
+use tape::entry::Source;
+use tape::event::Event;
+use tape::export::{self, Container, Flac, SampleFormat, Wav};
+use tape::factory::{FactoryState, RepeatMode, TranslateBehavior};
+use tape::io::SeekFrom;
+use tape::io::Tell;
+use tape::logger::LevelHandle;
+use tape::meta::Describe;
+use tape::output::Null;
+use tape::proto::ServerMessage;
+use tape::{Engine, Entry, Factory, Gain};
+use tape::{ErrorKind, QueueEntry, Request, Response, ResponseError, Status};
+use tracing::{debug, error, info, warn};
 
 fn main() {
     if let Err(e) = run() {
@@ -17,15 +41,26 @@ fn main() {
 }
 
 pub fn run() -> Result<()> {
-    tape::logger::init()?;
+    let level = tape::logger::init_reloadable()?;
+
+    let cli = Cli::parse();
+    let config = Config::load()?;
+
+    if let Some(filter) = config.log_level() {
+        level.modify(|level| *level = filter)?;
+    }
 
     debug!("Audio player daemon {}", env!("CARGO_PKG_VERSION"));
 
-    let dir = tape::runtime_dir()?;
-    let path = tape::socket_path()?;
+    let path = match config.socket() {
+        Some(path) => path.to_path_buf(),
+        None => tape::socket_path()?,
+    };
 
-    std::fs::create_dir_all(dir)
-        .with_context(|| format!("{}: failed to create runtime directory", path.display()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("{}: failed to create runtime directory", path.display()))?;
+    }
 
     if let Err(e) = std::fs::remove_file(&path) {
         match e.kind() {
@@ -42,134 +77,684 @@ pub fn run() -> Result<()> {
     let socket = UnixListener::bind(&path)
         .with_context(|| format!("{}: failed to bind to socket", path.display()))?;
 
-    let mut server = Server::new()?;
-    server.run(&socket);
+    let subscribers = Subscribers::default();
+    let (commands, queue) = std::sync::mpsc::channel();
+    let mut server = Server::new(cli.output, subscribers.clone(), commands.clone(), level)?;
+
+    // A saved session carries on from where the previous daemon left off, but whatever the
+    // config file sets still wins over the saved settings.
+    match Session::load() {
+        Ok(Some(session)) => server.restore(session),
+        Ok(None) => (),
+        Err(e) => warn!("{:#}", e),
+    }
+
+    server.apply(&config).map_err(|e| anyhow!(e))?;
+
+    for warning in server.warnings.drain(..) {
+        warn!("{}", warning);
+    }
+
+    let signals = Signals::new([SIGHUP]).context("failed to install signal handler")?;
+    let reload = commands.clone();
+
+    std::thread::Builder::new()
+        .name("signals".into())
+        .spawn(move || handle_signals(signals, reload))
+        .context("failed to spawn signal thread")?;
+
+    std::thread::Builder::new()
+        .name("listener".into())
+        .spawn(move || conn::listen(socket, commands, subscribers))
+        .context("failed to spawn listener thread")?;
+
+    server.run(queue);
 
     Ok(())
 }
 
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+type Provider = Gain<Factory<Entry>>;
+
+pub enum Message {
+    Command(Command),
+    Stream(EngineError),
+    Probed,
+    Persist,
+}
+
 struct Server {
-    engine: Engine<Factory<Sound>>,
-    buf: Vec<u8>,
+    engine: Engine<Provider>,
+    events: Sender<Event>,
+    messages: Sender<Message>,
+    level: LevelHandle,
+    library: Vec<PathBuf>,
+    warnings: Vec<String>,
+    session: Sender<Snapshot>,
+    saved: Option<Cursor>,
 }
 
 impl Server {
-    fn new() -> Result<Self> {
-        let factory = Factory::new();
-        let mut engine = Engine::new(factory)?;
+    fn new(
+        output: OutputKind,
+        subscribers: Subscribers,
+        messages: Sender<Message>,
+        level: LevelHandle,
+    ) -> Result<Self> {
+        let provider = Gain::new(Factory::new());
+        let mut engine = match output {
+            OutputKind::Device => Engine::<Provider>::new(provider)?,
+            OutputKind::Null => {
+                Engine::<Provider>::with_output(provider, Box::new(Null::default()))?
+            }
+        };
+        let (events, rx) = std::sync::mpsc::channel();
+        let (errors, failures) = std::sync::mpsc::channel();
+        engine.provider().inner().set_events(events.clone());
+        engine.set_events(events.clone());
+        engine.set_errors(errors);
         engine.run()?;
 
+        let persist = messages.clone();
+        std::thread::Builder::new()
+            .name("events".into())
+            .spawn(move || broadcast(rx, subscribers, persist))
+            .context("failed to spawn event thread")?;
+
+        // Stream errors wake the command loop instead of being polled for.
+        let wake = messages.clone();
+        std::thread::Builder::new()
+            .name("errors".into())
+            .spawn(move || {
+                for e in failures {
+                    if wake.send(Message::Stream(e)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .context("failed to spawn error thread")?;
+
         let server = Self {
             engine,
-            buf: Vec::new(),
+            events,
+            messages,
+            level,
+            library: Vec::new(),
+            warnings: Vec::new(),
+            session: Session::spawn()?,
+            saved: None,
         };
 
         Ok(server)
     }
 
-    fn serve(&mut self, con: &mut UnixStream) -> Result<()> {
-        self.buf.clear();
-        con.read_to_end(&mut self.buf)?;
+    fn factory(&self) -> &Factory<Entry> {
+        self.engine.provider().inner()
+    }
+
+    fn dispatch(&mut self, req: Request, reply: Sender<Response>) {
+        let result = match req {
+            Request::Export {
+                path,
+                format,
+                rate,
+                channels,
+                duration,
+            } => return self.export(path, format, rate, channels, duration, reply),
+            Request::Add { paths, at, next } => match self.add(paths, at, next) {
+                Ok(sources) => return self.probe(sources, Some(reply)),
+                Err(e) => Err(e),
+            },
+            req => self.handle(req),
+        };
+        let warnings = std::mem::take(&mut self.warnings);
+        let response = match result {
+            Ok(data) => Response::Success { data, warnings },
+            Err(e) => Err(e).into(),
+        };
+
+        let _ = reply.send(response);
+    }
+
+    fn add(
+        &mut self,
+        paths: Vec<PathBuf>,
+        at: Option<usize>,
+        next: bool,
+    ) -> Result<Vec<Arc<Source>>, ResponseError> {
+        let mut files = Vec::new();
+
+        for path in paths {
+            let path = self.resolve(path)?;
+
+            if path.is_file() {
+                files.push(path.clone());
+            } else {
+                let entries = std::fs::read_dir(&path)
+                    .with_context(|| format!("{}: failed to read directory", path.display()))
+                    .or_fail(ErrorKind::NotFound)?;
+
+                for entry in entries {
+                    let path = match process_entry(entry).context("failed to read path") {
+                        Ok(path) => path,
+                        Err(e) => {
+                            warn!("{:#}", e);
+                            continue;
+                        }
+                    };
+                    files.push(path);
+                }
+            }
+        }
+
+        let quality = self.factory().state().resample_quality().get();
+        let entries = files
+            .into_iter()
+            .map(|path| Entry::new(path, quality))
+            .collect::<Vec<_>>();
+        let sources = entries.iter().map(Entry::source).collect::<Vec<_>>();
+
+        match at {
+            Some(index) => self.factory().insert(index, entries),
+            None if next => self.factory().play_next(entries),
+            None => self.factory().extend(entries),
+        }
+
+        self.engine.state().set(PlaybackState::Playing);
 
-        let req =
-            serde_json::from_slice::<Request>(&self.buf).context("failed to accept request")?;
+        Ok(sources)
+    }
 
+    fn handle(&mut self, req: Request) -> Result<serde_json::Value, ResponseError> {
         match req {
-            Request::Add { paths } => {
-                let mut files = Vec::new();
+            Request::Add { .. } | Request::Export { .. } | Request::Subscribe => unreachable!(),
+            Request::Remove { ids } => {
+                // Audio already buffered only has to be dropped when the playing track goes away.
+                self.engine
+                    .flush_if(|provider| provider.inner().remove(&ids));
+            }
+            Request::Move { from, to } => self.factory().reorder(from, to),
+            Request::Clear => self.engine.flush_with(|provider| provider.inner().clear()),
+            Request::Config { props } => self.configure(props)?,
+            Request::Reload => {
+                let config = Config::load().or_fail(ErrorKind::Config)?;
+                self.apply(&config)?;
+            }
+            Request::Seek { pos } => {
+                if !self
+                    .engine
+                    .flush_with(|provider| provider.inner().seek(pos))
+                {
+                    let message = "failed to seek current track";
+                    return Err(ResponseError::new(ErrorKind::Seek, message));
+                }
+            }
+            Request::Jump { pos, relative } => {
+                let found = self.engine.flush_with(|provider| {
+                    let factory = provider.inner();
 
-                for path in paths {
-                    if path.is_file() {
-                        files.push(path.clone());
+                    if relative {
+                        factory.translate(pos, TranslateBehavior::Free)
+                    } else if let Ok(pos) = pos.try_into() {
+                        factory.select(pos)
                     } else {
-                        for entry in std::fs::read_dir(path)? {
-                            let path = match process_entry(entry).context("failed to read path") {
-                                Ok(path) => path,
-                                Err(e) => {
-                                    warn!("{:#}", e);
-                                    continue;
-                                }
-                            };
-                            files.push(path);
-                        }
+                        false
                     }
+                });
+
+                if !found {
+                    let message = format!("{}: no such track", pos);
+                    return Err(ResponseError::new(ErrorKind::NotFound, message));
                 }
+            }
+            Request::Status => {
+                let status = self.status();
+                return serde_json::to_value(status).or_fail(ErrorKind::Internal);
+            }
+            Request::Queue => {
+                let pos = self.factory().pos();
+                let entries = self.factory().inspect(|items| {
+                    items
+                        .iter()
+                        .enumerate()
+                        .map(|(index, item)| QueueEntry {
+                            index,
+                            path: item.path().into(),
+                            title: item.metadata().title().map(Into::into),
+                            artist: item.metadata().artist().map(Into::into),
+                            duration: item.duration(),
+                            current: index == pos,
+                        })
+                        .collect::<Vec<_>>()
+                });
+
+                return serde_json::to_value(entries).or_fail(ErrorKind::Internal);
+            }
+            Request::Play => self.engine.state().set(PlaybackState::Playing),
+            Request::Pause => self.engine.state().set(PlaybackState::Paused),
+            Request::Devices => {
+                let hosts = tape::device::hosts();
+                return serde_json::to_value(hosts).or_fail(ErrorKind::Internal);
+            }
+        }
 
-                for path in files {
-                    match probe_file(&path)
-                        .with_context(|| format!("{}: failed to probe file", path.display()))
-                    {
-                        Ok(sound) => self.engine.provider().map(|items| items.push(sound)),
-                        Err(e) => warn!("{:#}", e),
+        Ok(serde_json::Value::Null)
+    }
+
+    fn configure(&mut self, props: Vec<(String, String)>) -> Result<(), ResponseError> {
+        let mut state = self.factory().state();
+        let mut ser = serde_json::to_value(&*state).or_fail(ErrorKind::Internal)?;
+        let mut warnings = Vec::new();
+
+        for (key, value) in props {
+            let value = match key.as_str() {
+                "volume" => {
+                    let adjustment = value
+                        .parse()
+                        .with_context(|| format!("{}: invalid value", key))
+                        .or_fail(ErrorKind::InvalidValue)?;
+                    let mut volume = state.volume().get();
+
+                    if !volume.adjust(adjustment) {
+                        let level = volume.level() * 100.0;
+                        warnings.push(format!("{}: clamped to {:.0}%", key, level));
                     }
+
+                    serde_json::to_value(volume).or_fail(ErrorKind::Internal)?
                 }
+                "mute" if value == "toggle" => (!*state.mute()).into(),
+                "device" if value == "default" => serde_json::Value::Null,
+                _ => serde_json::from_str(&value).unwrap_or(value.into()),
+            };
 
-                self.engine.state().set(PlaybackState::Playing);
+            match ser.get_mut(&key) {
+                Some(prop) => *prop = value,
+                None => {
+                    let message = format!("{}: unknown property", key);
+                    return Err(ResponseError::new(ErrorKind::UnknownProperty, message));
+                }
             }
-            Request::Remove { ids } => self.engine.provider().map(|items| {
-                let mut d = 0;
+        }
 
-                for i in &ids {
-                    let i = match i.checked_sub(d) {
-                        Some(i) if i < items.len() => i,
-                        _ => continue,
-                    };
+        let de = serde_json::from_value::<FactoryState>(ser)
+            .context("failed to update state")
+            .or_fail(ErrorKind::InvalidValue)?;
+        drop(state);
+
+        self.replace_state(de)?;
+        self.warnings.extend(warnings);
+
+        Ok(())
+    }
+
+    fn replace_state(&mut self, de: FactoryState) -> Result<(), ResponseError> {
+        let mut state = self.factory().state();
+        let mut prev = state.replace(de);
+
+        let quality = state.resample_quality().get();
+        let device = state.device().clone();
+        drop(state);
+
+        self.factory().map(|items| {
+            for item in items {
+                item.set_quality(quality);
+            }
+        });
+        let _ = self.events.send(Event::ConfigChanged);
+
+        if device != *prev.device() {
+            if let Err(e) = self.engine.set_device(device.as_deref()) {
+                *self.factory().state().device() = prev.device().take();
+                return Err(e)
+                    .context("failed to switch device")
+                    .or_fail(ErrorKind::Device);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply(&mut self, config: &Config) -> Result<(), ResponseError> {
+        let path = Config::path().or_fail(ErrorKind::Config)?;
+        let fail = |e: ResponseError| {
+            let message = format!("{}: {}", path.display(), e.message);
+            ResponseError::new(e.kind, message)
+        };
+
+        self.configure(config.playback()).map_err(fail)?;
+
+        // Like at startup, the level is left alone unless the file sets one.
+        if let Some(filter) = config.log_level() {
+            self.level
+                .modify(|level| *level = filter)
+                .or_fail(ErrorKind::Internal)?;
+        }
 
-                    items.remove(i);
-                    d += 1;
+        self.library = config.library().to_vec();
+
+        Ok(())
+    }
+
+    fn persist(&self) {
+        let factory = self.factory();
+        let queue = Queue {
+            paths: factory.inspect(|items| items.iter().map(|item| item.path().into()).collect()),
+            settings: factory.state().clone(),
+        };
+
+        let _ = self.session.send(Snapshot::Queue(queue));
+    }
+
+    fn save(&mut self) {
+        let factory = self.factory();
+        let cursor = Cursor {
+            index: factory.pos(),
+            position: factory.position(),
+            state: self.engine.state().get(),
+        };
+
+        if self.saved.as_ref() != Some(&cursor) {
+            let _ = self.session.send(Snapshot::Cursor(cursor.clone()));
+            self.saved = Some(cursor);
+        }
+    }
+
+    fn restore(&mut self, session: Session) {
+        if let Err(e) = self.replace_state(session.queue.settings) {
+            warn!("failed to restore settings: {}", e);
+        }
+
+        let quality = self.factory().state().resample_quality().get();
+        let entries = session
+            .queue
+            .paths
+            .into_iter()
+            .map(|path| Entry::new(path, quality))
+            .collect::<Vec<_>>();
+        let sources = entries.iter().map(Entry::source).collect::<Vec<_>>();
+
+        // Playback stays paused whatever the saved state was, ready to resume at the same spot.
+        self.engine.flush_with(|provider| {
+            let factory = provider.inner();
+            factory.extend(entries);
+
+            if let Some(cursor) = session.cursor {
+                if let (true, Some(position)) = (factory.select(cursor.index), cursor.position) {
+                    factory.seek(SeekFrom::Start(position));
                 }
-            }),
-            Request::Config { props } => {
-                let mut state = self.engine.provider().state();
-                let mut ser = serde_json::to_value(&*state)?;
-
-                for (key, value) in props {
-                    if let Some(prop) = ser.get_mut(key) {
-                        *prop = value.into();
+            }
+        });
+
+        self.probe(sources, None);
+    }
+
+    fn probe(&self, sources: Vec<Arc<Source>>, reply: Option<Sender<Response>>) {
+        let events = self.events.clone();
+        let messages = self.messages.clone();
+        let fail = reply.clone();
+
+        // Probing may take a while, it runs on its own thread which replies once done.
+        let result = std::thread::Builder::new()
+            .name("probe".into())
+            .spawn(move || {
+                let count = sources.len();
+                let mut warnings = Vec::new();
+
+                for source in sources {
+                    if let Err(e) = source.probe() {
+                        let path = source.path().to_path_buf();
+                        let warning = format!("{}: failed to probe file: {}", path.display(), e);
+                        warn!("{}", warning);
+                        warnings.push(warning);
+
+                        let message = e.to_string();
+                        let _ = events.send(Event::ProbeFailed { path, message });
                     }
                 }
 
-                let de = serde_json::from_value::<FactoryState>(ser)
-                    .context("failed to update state")?;
-                state.replace(de);
-            }
-            Request::Seek { t } => self.engine.provider().seek(t),
-            Request::Jump { pos, relative } => {
-                if relative {
-                    self.engine
-                        .provider()
-                        .translate(pos, TranslateBehavior::Free);
-                } else if let Ok(pos) = pos.try_into() {
-                    self.engine.provider().select(pos);
+                let _ = messages.send(Message::Probed);
+
+                // The entries stay queued either way, but adding nothing playable is an error.
+                let response = match warnings.len() {
+                    n if n > 0 && n == count => {
+                        let message = warnings.join("\n");
+                        Err(ResponseError::new(ErrorKind::Probe, message)).into()
+                    }
+                    _ => Response::Success {
+                        data: serde_json::Value::Null,
+                        warnings,
+                    },
+                };
+
+                if let Some(reply) = reply {
+                    let _ = reply.send(response);
                 }
+            });
+
+        if let Err(e) = result {
+            warn!("failed to spawn probe thread: {}", e);
+
+            if let Some(fail) = fail {
+                let result = Err(e)
+                    .context("failed to spawn probe thread")
+                    .or_fail(ErrorKind::Internal);
+                let _ = fail.send(result.into());
             }
-            Request::Play => self.engine.state().set(PlaybackState::Playing),
-            Request::Pause => self.engine.state().set(PlaybackState::Paused),
         }
-        Ok(())
     }
 
-    fn run(&mut self, sock: &UnixListener) {
-        for con in sock.incoming() {
-            if let Err(e) = con
-                .context("failed to accept incoming connection")
-                .and_then(|mut con| self.serve(&mut con))
-            {
-                warn!("{:#}", e);
+    fn resolve(&self, path: PathBuf) -> Result<PathBuf, ResponseError> {
+        if path.is_absolute() {
+            return Ok(path);
+        }
+
+        // Relative paths name something inside one of the library directories.
+        self.library
+            .iter()
+            .map(|dir| dir.join(&path))
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                let message = format!("{}: not found in library", path.display());
+                ResponseError::new(ErrorKind::NotFound, message)
+            })
+    }
+
+    fn status(&mut self) -> Status {
+        let state = self.engine.state().get();
+        let factory = self.factory();
+        let pos = factory.pos();
+        let (path, metadata) = factory
+            .inspect(|items| {
+                items
+                    .get(pos)
+                    .map(|item| (item.path().into(), item.metadata().without_cover()))
+            })
+            .unzip();
+        let (position, duration) = match path {
+            Some(_) => (factory.position(), factory.duration()),
+            None => (None, None),
+        };
+        let mut settings = factory.state();
+
+        Status {
+            state,
+            index: path.is_some().then_some(pos),
+            path,
+            position,
+            duration,
+            repeat_mode: settings.repeat_mode().get(),
+            shuffle: settings.shuffle().get(),
+            volume: settings.volume().get(),
+            mute: *settings.mute(),
+            metadata,
+        }
+    }
+
+    fn export(
+        &mut self,
+        path: PathBuf,
+        format: SampleFormat,
+        rate: u32,
+        channels: usize,
+        duration: Option<f64>,
+        reply: Sender<Response>,
+    ) {
+        let factory = self.factory();
+        let queue = factory.inspect(|items| items.iter().map(|item| item.path().into()).collect());
+        let settings = factory.state().clone();
+        let fail = reply.clone();
+
+        // Rendering may take a while, it runs on its own thread which replies once done.
+        let result = std::thread::Builder::new()
+            .name("export".into())
+            .spawn(move || {
+                let result = export(&path, queue, settings, format, rate, channels, duration)
+                    .with_context(|| format!("{}: failed to export queue", path.display()))
+                    .or_fail(ErrorKind::Export)
+                    .map(|_| serde_json::Value::Null);
+
+                let _ = reply.send(result.into());
+            });
+
+        if let Err(e) = result {
+            let result = Err(e)
+                .context("failed to spawn export thread")
+                .or_fail(ErrorKind::Internal);
+            let _ = fail.send(result.into());
+        }
+    }
+
+    fn run(&mut self, queue: Receiver<Message>) {
+        let mut saved = Instant::now();
+
+        loop {
+            let deadline = match self.engine.retry_at() {
+                Some(retry) => std::cmp::min(retry, saved + SAVE_INTERVAL),
+                None => saved + SAVE_INTERVAL,
+            };
+
+            match queue.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Message::Command(Command { req, reply })) => self.dispatch(req, reply),
+                Ok(Message::Stream(e)) => self.engine.fail(e),
+                Ok(Message::Probed) => self.factory().regroup(),
+                Ok(Message::Persist) => self.persist(),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            if let Err(e) = self.engine.recover() {
+                error!("{:#}", e);
+            }
+
+            if saved.elapsed() >= SAVE_INTERVAL {
+                self.save();
+                saved = Instant::now();
             }
         }
     }
 }
 
-fn probe_file<P>(path: P) -> Result<Sound>
+fn broadcast(events: Receiver<Event>, subscribers: Subscribers, persist: Sender<Message>) {
+    for event in events {
+        // The queue file is only rewritten when something in it changed.
+        if let Event::QueueChanged | Event::ConfigChanged = event {
+            let _ = persist.send(Message::Persist);
+        }
+
+        // Connections that closed since the last event are dropped.
+        subscribers.lock().retain(|subscriber| {
+            subscriber
+                .upgrade()
+                .is_some_and(|tx| tx.send(ServerMessage::Event(event.clone())).is_ok())
+        });
+    }
+}
+
+fn handle_signals(mut signals: Signals, commands: Sender<Message>) {
+    for _ in signals.forever() {
+        let (reply, rx) = std::sync::mpsc::channel();
+        let req = Request::Reload;
+
+        if commands
+            .send(Message::Command(Command { req, reply }))
+            .is_err()
+        {
+            return;
+        }
+
+        let response = match rx.recv() {
+            Ok(response) => response,
+            Err(_) => return,
+        };
+
+        for warning in response.warnings() {
+            warn!("{}", warning);
+        }
+
+        match response.into_result() {
+            Ok(_) => info!("Reloaded configuration"),
+            Err(e) => error!("failed to reload configuration: {}", e),
+        }
+    }
+}
+
+fn export(
+    path: &Path,
+    queue: Vec<PathBuf>,
+    mut settings: FactoryState,
+    format: SampleFormat,
+    rate: u32,
+    channels: usize,
+    duration: Option<f64>,
+) -> Result<()> {
+    let container = Container::from_path(path).context("unknown container")?;
+
+    if rate == 0 || channels == 0 {
+        bail!("invalid output format");
+    }
+
+    if duration.is_none() && !matches!(settings.repeat_mode(), RepeatMode::Disabled) {
+        bail!("queue repeats endlessly, a duration is required");
+    }
+
+    // The queue is rendered from a factory of its own, so playback carries on untouched.
+    let quality = settings.resample_quality().get();
+    let factory = Factory::new();
+    factory.state().replace(settings);
+    factory.extend(queue.into_iter().map(|path| Entry::new(path, quality)));
+
+    let mut provider = Arc::new(Gain::new(factory));
+    let layout = Layout::with_count(channels);
+    let limit = duration.map(|secs| (secs.max(0.0) * rate as f64) as u64);
+    let file = BufWriter::new(File::create(path)?);
+    let frames = match container {
+        Container::Wav => Wav::new(file, format, layout, rate)
+            .and_then(|mut wav| export::render(&mut provider, &mut wav, layout, rate, limit)),
+        Container::Flac => Flac::new(file, format, layout, rate)
+            .and_then(|mut flac| export::render(&mut provider, &mut flac, layout, rate, limit)),
+    }?;
+
+    debug!(
+        "Exported {:.1}s of audio to {}",
+        frames as f64 / rate as f64,
+        path.display()
+    );
+
+    Ok(())
+}
+
+trait Fail<T> {
+    fn or_fail(self, kind: ErrorKind) -> Result<T, ResponseError>;
+}
+
+impl<T, E> Fail<T> for Result<T, E>
 where
-    P: AsRef<Path>,
+    E: Into<anyhow::Error>,
 {
-    let path = path.as_ref();
-    let file = File::open(path)?;
-    let sound = Sound::new(file)?;
-
-    Ok(sound)
+    fn or_fail(self, kind: ErrorKind) -> Result<T, ResponseError> {
+        self.map_err(|e| ResponseError::new(kind, format!("{:#}", e.into())))
+    }
 }
 
 fn process_entry(entry: std::io::Result<DirEntry>) -> Result<PathBuf> {
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Duration;
+use tape::engine::PlaybackState;
+use tape::factory::FactoryState;
+use tracing::warn;
+
+const QUEUE: &str = "queue.json";
+const CURSOR: &str = "position.json";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Queue {
+    pub paths: Vec<PathBuf>,
+    pub settings: FactoryState,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Cursor {
+    pub index: usize,
+    pub position: Option<Duration>,
+    pub state: PlaybackState,
+}
+
+pub struct Session {
+    pub queue: Queue,
+    pub cursor: Option<Cursor>,
+}
+
+pub enum Snapshot {
+    Queue(Queue),
+    Cursor(Cursor),
+}
+
+impl Session {
+    pub fn load() -> Result<Option<Self>> {
+        let queue = match load(QUEUE)? {
+            Some(queue) => queue,
+            None => return Ok(None),
+        };
+
+        // Losing the position only loses the spot in the queue.
+        let cursor = load(CURSOR).unwrap_or_else(|e| {
+            warn!("{:#}", e);
+            None
+        });
+
+        Ok(Some(Self { queue, cursor }))
+    }
+
+    pub fn spawn() -> Result<Sender<Snapshot>> {
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("session".into())
+            .spawn(move || write_snapshots(rx))
+            .context("failed to spawn session thread")?;
+
+        Ok(tx)
+    }
+}
+
+fn write_snapshots(rx: Receiver<Snapshot>) {
+    while let Ok(snapshot) = rx.recv() {
+        let mut queue = None;
+        let mut cursor = None;
+
+        // Only the latest snapshot of each file is worth writing.
+        for snapshot in std::iter::once(snapshot).chain(rx.try_iter()) {
+            match snapshot {
+                Snapshot::Queue(snapshot) => queue = Some(snapshot),
+                Snapshot::Cursor(snapshot) => cursor = Some(snapshot),
+            }
+        }
+
+        if let Some(Err(e)) = queue.map(|queue| store(QUEUE, &queue)) {
+            warn!("{:#}", e);
+        }
+
+        if let Some(Err(e)) = cursor.map(|cursor| store(CURSOR, &cursor)) {
+            warn!("{:#}", e);
+        }
+    }
+}
+
+fn path(name: &str) -> Result<PathBuf> {
+    let mut path = dirs::state_dir().context("failed to determine state directory")?;
+    path.push("tape");
+    path.push(name);
+    Ok(path)
+}
+
+fn load<T: DeserializeOwned>(name: &str) -> Result<Option<T>> {
+    let path = path(name)?;
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("{}: failed to read session", path.display()))
+        }
+    };
+
+    serde_json::from_slice(&data)
+        .map(Some)
+        .with_context(|| format!("{}: invalid session", path.display()))
+}
+
+fn store<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let path = path(name)?;
+    let tmp = path.with_extension("json.tmp");
+    let data = serde_json::to_vec(value)?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("{}: failed to create state directory", dir.display()))?;
+    }
+
+    // The previous session stays intact until the new one is fully on disk.
+    let mut file = File::create(&tmp)
+        .with_context(|| format!("{}: failed to create session", tmp.display()))?;
+    file.write_all(&data)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("{}: failed to write session", tmp.display()))?;
+    std::fs::rename(&tmp, &path)
+        .with_context(|| format!("{}: failed to replace session", path.display()))
+}
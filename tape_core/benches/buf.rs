@@ -0,0 +1,143 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tape_core::buf::{proxy, Buf, BufMut, Layout, Seq, Spec};
+use tape_core::io::Write;
+
+const FRAMES: usize = 4096;
+const RATE: u32 = 48000;
+
+fn seq(layout: Layout) -> Seq<f32> {
+    let mut seq = Seq::with_spec(Spec::new(FRAMES, layout, RATE));
+
+    for (i, mut frame) in seq.frames_mut().enumerate() {
+        for sample in frame.iter_mut() {
+            *sample = (i as f32 * 0.01).sin();
+        }
+    }
+
+    seq.set_len(FRAMES);
+    seq
+}
+
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+
+    for layout in [Layout::STEREO, Layout::SURROUND_5_1] {
+        let id = layout.count();
+        let mut src = seq(layout);
+        let mut raw = vec![0.0f32; FRAMES * layout.count()];
+
+        group.bench_with_input(BenchmarkId::new("frames/seq", id), &src, |b, src| {
+            b.iter(|| {
+                let mut acc = 0.0;
+
+                for frame in src.frames() {
+                    for sample in frame.iter() {
+                        acc += *sample;
+                    }
+                }
+
+                black_box(acc)
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("frames_mut/seq", id), |b| {
+            b.iter(|| {
+                for mut frame in src.frames_mut() {
+                    for sample in frame.iter_mut() {
+                        *sample *= 0.5;
+                    }
+                }
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("frames_mut/int", id), |b| {
+            let spec = Spec::new(FRAMES, layout, RATE);
+
+            b.iter(|| {
+                let mut dst = proxy::int_mut(&mut raw, spec);
+
+                for mut frame in dst.frames_mut() {
+                    for sample in frame.iter_mut() {
+                        *sample *= 0.5;
+                    }
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+
+    for layout in [Layout::STEREO, Layout::SURROUND_5_1] {
+        let id = layout.count();
+        let spec = Spec::new(FRAMES, layout, RATE);
+        let mut src = seq(layout);
+        let planes = (0..layout.count())
+            .map(|_| vec![0.25f32; FRAMES])
+            .collect::<Vec<_>>();
+        let planes = planes.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let mut seq_dst = Seq::<f32>::with_spec(spec);
+        let mut f32_dst = vec![0.0f32; FRAMES * layout.count()];
+        let mut i16_dst = vec![0i16; FRAMES * layout.count()];
+
+        group.bench_function(BenchmarkId::new("seq/seq", id), |b| {
+            b.iter(|| {
+                src.set_pos(0);
+                seq_dst.set_len(0);
+                black_box(src.write(&mut seq_dst))
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("seq/int-f32", id), |b| {
+            b.iter(|| {
+                src.set_pos(0);
+                black_box(src.write(&mut proxy::int_mut(&mut f32_dst, spec)))
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("seq/int-i16", id), |b| {
+            b.iter(|| {
+                src.set_pos(0);
+                black_box(src.write(&mut proxy::int_mut(&mut i16_dst, spec)))
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("dy/seq", id), |b| {
+            b.iter(|| {
+                seq_dst.set_len(0);
+                black_box(proxy::dy(&planes, spec).write(&mut seq_dst))
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("int/int", id), |b| {
+            let mut raw = vec![0.5f32; FRAMES * layout.count()];
+
+            b.iter(|| {
+                let mut int = proxy::int_mut(&mut raw, spec);
+                int.set_len(FRAMES);
+                black_box(int.write(&mut proxy::int_mut(&mut f32_dst, spec)))
+            })
+        });
+    }
+
+    group.bench_function("seq/seq-mix", |b| {
+        let mut src = seq(Layout::SURROUND_5_1);
+        let mut dst = Seq::<f32>::with_spec(Spec::new(FRAMES, Layout::STEREO, RATE));
+
+        b.iter(|| {
+            src.set_pos(0);
+            dst.set_len(0);
+            black_box(src.write(&mut dst))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, iterate, write);
+criterion_main!(benches);
@@ -1,19 +1,29 @@
 pub mod iter;
+pub mod layout;
 pub mod proxy;
 pub mod seq;
 
+pub use layout::Layout;
 pub use seq::Seq;
 
-use iter::{Frame, FrameMut, Frames, FramesMut};
+use cpal::{FromSample, Sample};
+use iter::{Frame, FrameMut, Frames, FramesMut, Repr, ReprMut};
 
 pub trait Buf {
     type Item;
 
     fn spec(&self) -> Spec;
 
-    fn frame(&self, n: usize) -> Frame<'_, Self::Item>;
+    fn repr(&self) -> Repr<'_, Self::Item>;
 
-    fn frames(&self) -> Frames<'_, Self::Item>;
+    fn frame(&self, n: usize) -> Frame<'_, Self::Item> {
+        assert!(n < self.spec().frames());
+        Frame::new(self.repr(), n)
+    }
+
+    fn frames(&self) -> Frames<'_, Self::Item> {
+        Frames::new(self.repr(), self.spec().frames())
+    }
 
     fn pos(&self) -> usize;
 
@@ -27,22 +37,33 @@ pub trait Buf {
 }
 
 pub trait BufMut: Buf {
-    fn frame_mut(&mut self, n: usize) -> FrameMut<'_, Self::Item>;
+    fn repr_mut(&mut self) -> ReprMut<'_, Self::Item>;
+
+    fn frame_mut(&mut self, n: usize) -> FrameMut<'_, Self::Item> {
+        FrameMut::new(self.repr_mut(), n)
+    }
 
-    fn frames_mut(&mut self) -> FramesMut<'_, Self::Item>;
+    fn frames_mut(&mut self) -> FramesMut<'_, Self::Item> {
+        FramesMut::new(self.repr_mut())
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Spec {
     frames: usize,
-    channels: usize,
+    layout: Layout,
+    rate: u32,
 }
 
 impl Spec {
-    pub fn new(frames: usize, channels: usize) -> Self {
+    pub fn new(frames: usize, layout: Layout, rate: u32) -> Self {
         assert!(frames > 0);
-        assert!(channels > 0);
-        Self { frames, channels }
+        assert!(rate > 0);
+        Self {
+            frames,
+            layout,
+            rate,
+        }
     }
 
     pub fn frames(&self) -> usize {
@@ -50,6 +71,106 @@ impl Spec {
     }
 
     pub fn channels(&self) -> usize {
-        self.channels
+        self.layout.count()
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+}
+
+pub fn copy<T, U>(src: &T, dst: &mut U) -> usize
+where
+    T: Buf,
+    T::Item: Sample,
+    U: BufMut,
+    U::Item: Sample + FromSample<T::Item>,
+{
+    let p1 = src.pos();
+    let p2 = dst.len();
+    let n = std::cmp::min(src.len() - p1, dst.spec().frames() - p2);
+    let channels = std::cmp::min(src.spec().channels(), dst.spec().channels());
+
+    if n == 0 {
+        return 0;
+    }
+
+    let src = src.repr();
+
+    match (src, dst.repr_mut()) {
+        (
+            Repr::Interleaved {
+                buf: a,
+                channels: m,
+            },
+            ReprMut::Interleaved {
+                buf: b,
+                channels: k,
+            },
+        ) if m == k => {
+            convert(&a[p1 * m..(p1 + n) * m], &mut b[p2 * k..(p2 + n) * k]);
+        }
+        (Repr::Interleaved { .. }, ReprMut::Planar { buf: b, frames: g }) => {
+            for (c, b) in b.chunks_mut(g).take(channels).enumerate() {
+                for (i, b) in b[p2..p2 + n].iter_mut().enumerate() {
+                    if let Some(a) = Frame::new(src, p1 + i).get(c) {
+                        *b = U::Item::from_sample(*a);
+                    }
+                }
+            }
+        }
+        (
+            Repr::Interleaved { .. },
+            ReprMut::Interleaved {
+                buf: b,
+                channels: k,
+            },
+        ) => {
+            for (i, b) in b[p2 * k..(p2 + n) * k].chunks_exact_mut(k).enumerate() {
+                for (b, a) in b.iter_mut().zip(Frame::new(src, p1 + i).iter()) {
+                    *b = U::Item::from_sample(*a);
+                }
+            }
+        }
+        (_, ReprMut::Planar { buf: b, frames: g }) => {
+            for (c, b) in b.chunks_mut(g).take(channels).enumerate() {
+                if let Some(a) = src.channel(c) {
+                    convert(&a[p1..p1 + n], &mut b[p2..p2 + n]);
+                }
+            }
+        }
+        (
+            _,
+            ReprMut::Interleaved {
+                buf: b,
+                channels: k,
+            },
+        ) => {
+            let b = &mut b[p2 * k..(p2 + n) * k];
+
+            for c in 0..channels {
+                if let Some(a) = src.channel(c) {
+                    for (b, a) in b[c..].iter_mut().step_by(k).zip(&a[p1..p1 + n]) {
+                        *b = U::Item::from_sample(*a);
+                    }
+                }
+            }
+        }
+    }
+
+    n
+}
+
+fn convert<T, U>(src: &[T], dst: &mut [U])
+where
+    T: Sample,
+    U: Sample + FromSample<T>,
+{
+    for (dst, src) in dst.iter_mut().zip(src) {
+        *dst = U::from_sample(*src);
     }
 }
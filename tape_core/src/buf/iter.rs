@@ -1,26 +1,54 @@
-use crate::buf::{Buf, BufMut};
 use std::marker::PhantomData;
 
-pub struct Frames<'a, T>
-where
-    T: 'a,
-{
-    start: Box<[*mut T]>,
-    end: Box<[*mut T]>,
-    step: usize,
-    _marker: PhantomData<&'a T>,
+pub enum Repr<'a, T> {
+    Planar { buf: &'a [T], frames: usize },
+    Interleaved { buf: &'a [T], channels: usize },
+    Planes(&'a [&'a [T]]),
+}
+
+pub enum ReprMut<'a, T> {
+    Planar { buf: &'a mut [T], frames: usize },
+    Interleaved { buf: &'a mut [T], channels: usize },
+}
+
+impl<T> Clone for Repr<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Repr<'_, T> {}
+
+impl<'a, T> Repr<'a, T> {
+    pub fn channel(&self, channel: usize) -> Option<&'a [T]> {
+        match *self {
+            Self::Planar { buf, frames } => buf.chunks(frames).nth(channel),
+            Self::Interleaved { .. } => None,
+            Self::Planes(planes) => planes.get(channel).copied(),
+        }
+    }
+
+    fn channels(&self) -> usize {
+        match self {
+            Self::Planar { buf, frames } => buf.len() / frames,
+            Self::Interleaved { channels, .. } => *channels,
+            Self::Planes(planes) => planes.len(),
+        }
+    }
+}
+
+pub struct Frames<'a, T> {
+    repr: Repr<'a, T>,
+    start: usize,
+    end: usize,
 }
 
 impl<'a, T> Frames<'a, T> {
-    pub fn new<U>(buf: &U, step: usize) -> Self
-    where
-        U: Buf<Item = T>,
-    {
+    pub fn new(repr: Repr<'a, T>, frames: usize) -> Self {
         Self {
-            start: buf.frame(0).into(),
-            end: buf.frame(buf.spec().frames()).into(),
-            step,
-            _marker: PhantomData,
+            repr,
+            start: 0,
+            end: frames,
         }
     }
 }
@@ -29,40 +57,80 @@ impl<'a, T> Iterator for Frames<'a, T> {
     type Item = Frame<'a, T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.start == self.end {
+        if self.start >= self.end {
             return None;
         }
-        let frame = Frame::new(self.start.clone());
-        for ptr in self.start.iter_mut() {
-            unsafe {
-                *ptr = ptr.add(self.step);
-            }
-        }
+
+        let frame = Frame::new(self.repr, self.start);
+        self.start += 1;
+
         Some(frame)
     }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.start = self.start.saturating_add(n).min(self.end);
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.end - self.start;
+        (n, Some(n))
+    }
 }
 
+impl<T> ExactSizeIterator for Frames<'_, T> {}
+
 pub struct Frame<'a, T> {
-    frame: Box<[*mut T]>,
-    _marker: PhantomData<&'a T>,
+    repr: Repr<'a, T>,
+    n: usize,
+}
+
+impl<T> Clone for Frame<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
+impl<T> Copy for Frame<'_, T> {}
+
 impl<'a, T> Frame<'a, T> {
-    pub fn new(frame: Box<[*mut T]>) -> Self {
-        Self {
-            frame,
-            _marker: PhantomData,
+    pub fn new(repr: Repr<'a, T>, n: usize) -> Self {
+        Self { repr, n }
+    }
+
+    pub fn len(&self) -> usize {
+        self.repr.channels()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, channel: usize) -> Option<&'a T> {
+        match self.repr {
+            Repr::Planar { buf, frames } => buf.get(channel * frames + self.n),
+            Repr::Interleaved { buf, channels } if channel < channels => {
+                buf.get(self.n * channels + channel)
+            }
+            Repr::Interleaved { .. } => None,
+            Repr::Planes(planes) => planes.get(channel).and_then(|plane| plane.get(self.n)),
         }
     }
 
-    pub fn into_raw(self) -> Box<[*mut T]> {
-        self.frame
+    pub fn as_slice(&self) -> Option<&'a [T]> {
+        match self.repr {
+            Repr::Interleaved { buf, channels } => {
+                buf.get(self.n * channels..(self.n + 1) * channels)
+            }
+            _ => None,
+        }
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &'_ T> {
-        self.frame
-            .iter()
-            .map(|ptr| unsafe { ptr.as_ref_unchecked() })
+    pub fn iter(&self) -> Samples<'a, T> {
+        Samples {
+            frame: *self,
+            channel: 0,
+        }
     }
 }
 
@@ -75,42 +143,67 @@ where
     }
 }
 
-impl<'a, T> From<Frame<'a, T>> for Box<[*mut T]> {
-    fn from(frame: Frame<'a, T>) -> Self {
-        frame.into_raw()
-    }
+pub struct Samples<'a, T> {
+    frame: Frame<'a, T>,
+    channel: usize,
 }
 
-impl<'a, T> From<Vec<*mut T>> for Frame<'a, T> {
-    fn from(frame: Vec<*mut T>) -> Self {
-        Self::new(frame.into())
+impl<'a, T> Iterator for Samples<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.frame.get(self.channel)?;
+        self.channel += 1;
+
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.frame.len().saturating_sub(self.channel);
+        (n, Some(n))
     }
 }
 
-pub struct FramesMut<'a, T>
-where
-    T: 'a,
-{
-    start: Box<[*mut T]>,
-    end: Box<[*mut T]>,
+impl<T> ExactSizeIterator for Samples<'_, T> {}
+
+pub struct FramesMut<'a, T> {
+    ptr: *mut T,
+    channels: usize,
+    stride: usize,
     step: usize,
+    start: usize,
+    end: usize,
     _marker: PhantomData<&'a mut T>,
 }
 
 impl<'a, T> FramesMut<'a, T> {
-    pub fn new<U>(buf: &mut U, step: usize) -> Self
-    where
-        U: Buf<Item = T> + BufMut,
-    {
-        let end = unsafe {
-            let frame = buf.frame_mut(buf.spec().frames());
-            std::mem::transmute::<FrameMut<'_, T>, FrameMut<'_, T>>(frame)
-        };
-        let start = buf.frame_mut(0);
+    pub fn new(repr: ReprMut<'a, T>) -> Self {
+        match repr {
+            ReprMut::Planar { buf, frames } => Self::planar(buf, frames),
+            ReprMut::Interleaved { buf, channels } => Self::interleaved(buf, channels),
+        }
+    }
+
+    pub fn planar(buf: &'a mut [T], frames: usize) -> Self {
+        Self {
+            ptr: buf.as_mut_ptr(),
+            channels: buf.len() / frames,
+            stride: frames,
+            step: 1,
+            start: 0,
+            end: frames,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn interleaved(buf: &'a mut [T], channels: usize) -> Self {
         Self {
-            start: start.into(),
-            end: end.into(),
-            step,
+            ptr: buf.as_mut_ptr(),
+            channels,
+            stride: 1,
+            step: channels,
+            start: 0,
+            end: buf.len() / channels,
             _marker: PhantomData,
         }
     }
@@ -123,48 +216,133 @@ impl<'a, T> Iterator for FramesMut<'a, T> {
         if self.start >= self.end {
             return None;
         }
-        let frame = FrameMut::new(self.start.clone());
-        for ptr in self.start.iter_mut() {
-            unsafe {
-                *ptr = ptr.add(self.step);
-            }
-        }
+
+        // Every frame covers a distinct set of samples, so handing out several at once never
+        // aliases.
+        let frame = FrameMut {
+            ptr: unsafe { self.ptr.add(self.start * self.step) },
+            channels: self.channels,
+            stride: self.stride,
+            _marker: PhantomData,
+        };
+        self.start += 1;
+
         Some(frame)
     }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.start = self.start.saturating_add(n).min(self.end);
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.end - self.start;
+        (n, Some(n))
+    }
 }
 
+impl<T> ExactSizeIterator for FramesMut<'_, T> {}
+
 pub struct FrameMut<'a, T> {
-    frame: Box<[*mut T]>,
+    ptr: *mut T,
+    channels: usize,
+    stride: usize,
     _marker: PhantomData<&'a mut T>,
 }
 
 impl<'a, T> FrameMut<'a, T> {
-    pub fn new(frame: Box<[*mut T]>) -> Self {
+    pub fn new(repr: ReprMut<'a, T>, n: usize) -> Self {
+        match repr {
+            ReprMut::Planar { buf, frames } => Self::planar(buf, frames, n),
+            ReprMut::Interleaved { buf, channels } => Self::interleaved(buf, channels, n),
+        }
+    }
+
+    pub fn planar(buf: &'a mut [T], frames: usize, n: usize) -> Self {
+        assert!(n < frames);
         Self {
-            frame,
+            ptr: unsafe { buf.as_mut_ptr().add(n) },
+            channels: buf.len() / frames,
+            stride: frames,
             _marker: PhantomData,
         }
     }
 
-    pub fn into_raw(self) -> Box<[*mut T]> {
-        self.frame
+    pub fn interleaved(buf: &'a mut [T], channels: usize, n: usize) -> Self {
+        Self::from_slice(&mut buf[n * channels..(n + 1) * channels])
     }
 
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &'_ mut T> {
-        self.frame
-            .iter_mut()
-            .map(|ptr| unsafe { ptr.as_mut_unchecked() })
+    pub fn from_slice(buf: &'a mut [T]) -> Self {
+        Self {
+            ptr: buf.as_mut_ptr(),
+            channels: buf.len(),
+            stride: 1,
+            _marker: PhantomData,
+        }
     }
-}
 
-impl<'a, T> From<FrameMut<'a, T>> for Box<[*mut T]> {
-    fn from(frame: FrameMut<'a, T>) -> Self {
-        frame.into_raw()
+    pub fn len(&self) -> usize {
+        self.channels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels == 0
+    }
+
+    pub fn get_mut(&mut self, channel: usize) -> Option<&mut T> {
+        if channel >= self.channels {
+            return None;
+        }
+
+        Some(unsafe { &mut *self.ptr.add(channel * self.stride) })
+    }
+
+    pub fn as_mut_slice(&mut self) -> Option<&mut [T]> {
+        if self.stride != 1 {
+            return None;
+        }
+
+        Some(unsafe { std::slice::from_raw_parts_mut(self.ptr, self.channels) })
+    }
+
+    pub fn iter_mut(&mut self) -> SamplesMut<'_, T> {
+        SamplesMut {
+            ptr: self.ptr,
+            stride: self.stride,
+            remaining: self.channels,
+            _marker: PhantomData,
+        }
     }
 }
 
-impl<'a, T> From<Vec<*mut T>> for FrameMut<'a, T> {
-    fn from(frame: Vec<*mut T>) -> Self {
-        Self::new(frame.into())
+pub struct SamplesMut<'a, T> {
+    ptr: *mut T,
+    stride: usize,
+    remaining: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for SamplesMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let sample = unsafe { &mut *self.ptr };
+        self.remaining -= 1;
+
+        if self.remaining > 0 {
+            self.ptr = unsafe { self.ptr.add(self.stride) };
+        }
+
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
 }
+
+impl<T> ExactSizeIterator for SamplesMut<'_, T> {}
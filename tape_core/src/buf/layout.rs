@@ -0,0 +1,89 @@
+use std::ops::BitOr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout(u32);
+
+impl Layout {
+    pub const FRONT_LEFT: Self = Self(0x0001);
+    pub const FRONT_RIGHT: Self = Self(0x0002);
+    pub const FRONT_CENTER: Self = Self(0x0004);
+    pub const LOW_FREQUENCY: Self = Self(0x0008);
+    pub const BACK_LEFT: Self = Self(0x0010);
+    pub const BACK_RIGHT: Self = Self(0x0020);
+    pub const FRONT_LEFT_CENTER: Self = Self(0x0040);
+    pub const FRONT_RIGHT_CENTER: Self = Self(0x0080);
+    pub const BACK_CENTER: Self = Self(0x0100);
+    pub const SIDE_LEFT: Self = Self(0x0200);
+    pub const SIDE_RIGHT: Self = Self(0x0400);
+
+    pub const MONO: Self = Self::FRONT_CENTER;
+    pub const STEREO: Self = Self(Self::FRONT_LEFT.0 | Self::FRONT_RIGHT.0);
+    pub const SURROUND_2_1: Self = Self(Self::STEREO.0 | Self::LOW_FREQUENCY.0);
+    pub const QUAD: Self = Self(Self::STEREO.0 | Self::BACK_LEFT.0 | Self::BACK_RIGHT.0);
+    pub const SURROUND_5_0: Self = Self(Self::QUAD.0 | Self::FRONT_CENTER.0);
+    pub const SURROUND_5_1: Self = Self(Self::SURROUND_5_0.0 | Self::LOW_FREQUENCY.0);
+    pub const SURROUND_6_1: Self = Self(
+        Self::STEREO.0
+            | Self::FRONT_CENTER.0
+            | Self::LOW_FREQUENCY.0
+            | Self::BACK_CENTER.0
+            | Self::SIDE_LEFT.0
+            | Self::SIDE_RIGHT.0,
+    );
+    pub const SURROUND_7_1: Self =
+        Self(Self::SURROUND_5_1.0 | Self::SIDE_LEFT.0 | Self::SIDE_RIGHT.0);
+
+    pub fn from_bits(bits: u32) -> Self {
+        assert!(bits != 0);
+        Self(bits)
+    }
+
+    pub fn with_count(channels: usize) -> Self {
+        assert!(channels > 0);
+        match channels {
+            1 => Self::MONO,
+            2 => Self::STEREO,
+            3 => Self::SURROUND_2_1,
+            4 => Self::QUAD,
+            5 => Self::SURROUND_5_0,
+            6 => Self::SURROUND_5_1,
+            7 => Self::SURROUND_6_1,
+            8 => Self::SURROUND_7_1,
+            n if n < 32 => Self((1 << n) - 1),
+            _ => Self(u32::MAX),
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn index(&self, position: Self) -> Option<usize> {
+        self.iter().position(|p| p == position)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Self> {
+        let bits = self.0;
+
+        (0..u32::BITS)
+            .map(|i| 1 << i)
+            .filter(move |bit| bits & bit != 0)
+            .map(Self)
+    }
+}
+
+impl BitOr for Layout {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
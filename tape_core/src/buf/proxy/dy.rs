@@ -1,4 +1,4 @@
-use crate::buf::iter::{Frame, Frames};
+use crate::buf::iter::Repr;
 use crate::buf::{Buf, Spec};
 
 pub struct DyRef<'a, T> {
@@ -26,21 +26,8 @@ impl<'a, T> Buf for DyRef<'a, T> {
         self.spec
     }
 
-    fn frame(&self, n: usize) -> Frame<'_, Self::Item> {
-        let frames = self.spec().frames();
-        let channels = self.spec().channels();
-        assert!(n <= frames);
-        let mut frame = Vec::with_capacity(channels);
-        for channel in self.buf {
-            unsafe {
-                frame.push(channel.as_ptr().cast_mut().add(n));
-            }
-        }
-        frame.into()
-    }
-
-    fn frames(&self) -> Frames<'_, Self::Item> {
-        Frames::new(self, 1)
+    fn repr(&self) -> Repr<'_, T> {
+        Repr::Planes(&self.buf[..self.spec.channels()])
     }
 
     fn pos(&self) -> usize {
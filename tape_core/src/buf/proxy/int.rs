@@ -1,4 +1,4 @@
-use crate::buf::iter::{Frame, FrameMut, Frames, FramesMut};
+use crate::buf::iter::{Repr, ReprMut};
 use crate::buf::{Buf, BufMut, Spec};
 
 pub struct IntMut<'a, T> {
@@ -26,23 +26,13 @@ impl<T> Buf for IntMut<'_, T> {
         self.spec
     }
 
-    fn frame(&self, n: usize) -> Frame<'_, Self::Item> {
-        let frames = self.spec().frames();
-        let channels = self.spec().channels();
-        assert!(n <= frames);
-        let mut frame = Vec::with_capacity(channels);
+    fn repr(&self) -> Repr<'_, T> {
+        let channels = self.spec.channels();
 
-        for channel in self.buf.iter().take(channels) {
-            frame.push(unsafe { (channel as *const T as *mut T).add(n * channels) })
+        Repr::Interleaved {
+            buf: &self.buf[..self.spec.frames() * channels],
+            channels,
         }
-
-        Frame::new(frame.into())
-    }
-
-    fn frames(&self) -> Frames<'_, Self::Item> {
-        let channels = self.spec().channels();
-
-        Frames::new(self, channels)
     }
 
     fn pos(&self) -> usize {
@@ -67,22 +57,12 @@ impl<T> Buf for IntMut<'_, T> {
 }
 
 impl<T> BufMut for IntMut<'_, T> {
-    fn frame_mut(&mut self, n: usize) -> FrameMut<'_, Self::Item> {
-        let frames = self.spec().frames();
-        let channels = self.spec().channels();
-        assert!(n <= frames);
-        let mut frame = Vec::with_capacity(channels);
+    fn repr_mut(&mut self) -> ReprMut<'_, T> {
+        let channels = self.spec.channels();
 
-        for channel in self.buf.iter_mut().take(channels) {
-            frame.push(unsafe { (channel as *mut T).add(n * channels) })
+        ReprMut::Interleaved {
+            buf: &mut self.buf[..self.spec.frames() * channels],
+            channels,
         }
-
-        FrameMut::new(frame.into())
-    }
-
-    fn frames_mut(&mut self) -> FramesMut<'_, Self::Item> {
-        let channels = self.spec().channels();
-
-        FramesMut::new(self, channels)
     }
 }
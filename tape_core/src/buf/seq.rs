@@ -1,4 +1,4 @@
-use crate::buf::iter::{Frame, FrameMut, Frames, FramesMut};
+use crate::buf::iter::{Repr, ReprMut};
 use crate::buf::{Buf, BufMut, Spec};
 use cpal::Sample;
 
@@ -34,21 +34,11 @@ impl<T> Buf for Seq<T> {
         self.spec
     }
 
-    fn frame(&self, n: usize) -> Frame<'_, T> {
-        let frames = self.spec().frames();
-        let channels = self.spec().channels();
-        assert!(n <= frames);
-        let mut frame = Vec::with_capacity(channels);
-
-        for channel in self.buf.chunks(frames) {
-            frame.push(unsafe { channel.as_ptr().cast_mut().add(n) })
+    fn repr(&self) -> Repr<'_, T> {
+        Repr::Planar {
+            buf: &self.buf,
+            frames: self.spec.frames(),
         }
-
-        frame.into()
-    }
-
-    fn frames(&self) -> Frames<'_, T> {
-        Frames::new(self, 1)
     }
 
     fn pos(&self) -> usize {
@@ -73,20 +63,10 @@ impl<T> Buf for Seq<T> {
 }
 
 impl<T> BufMut for Seq<T> {
-    fn frame_mut(&mut self, n: usize) -> FrameMut<'_, Self::Item> {
-        let frames = self.spec().frames();
-        let channels = self.spec().channels();
-        assert!(n <= frames);
-        let mut frame = Vec::with_capacity(channels);
-
-        for channel in self.buf.chunks_mut(frames) {
-            frame.push(unsafe { channel.as_mut_ptr().add(n) })
+    fn repr_mut(&mut self) -> ReprMut<'_, T> {
+        ReprMut::Planar {
+            buf: &mut self.buf,
+            frames: self.spec.frames(),
         }
-
-        frame.into()
-    }
-
-    fn frames_mut(&mut self) -> FramesMut<'_, Self::Item> {
-        FramesMut::new(self, 1)
     }
 }
@@ -0,0 +1,173 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{Device, Host, SupportedStreamConfigRange};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub struct HostInfo {
+    name: String,
+    default: bool,
+    devices: Vec<DeviceInfo>,
+}
+
+impl HostInfo {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    pub fn devices(&self) -> &[DeviceInfo] {
+        &self.devices
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub struct DeviceInfo {
+    name: String,
+    default: bool,
+    configs: Vec<ConfigInfo>,
+}
+
+impl DeviceInfo {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    pub fn configs(&self) -> &[ConfigInfo] {
+        &self.configs
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub struct ConfigInfo {
+    channels: u16,
+    min_rate: u32,
+    max_rate: u32,
+    format: String,
+}
+
+impl ConfigInfo {
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn min_rate(&self) -> u32 {
+        self.min_rate
+    }
+
+    pub fn max_rate(&self) -> u32 {
+        self.max_rate
+    }
+
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+}
+
+impl From<SupportedStreamConfigRange> for ConfigInfo {
+    fn from(config: SupportedStreamConfigRange) -> Self {
+        Self {
+            channels: config.channels(),
+            min_rate: config.min_sample_rate().0,
+            max_rate: config.max_sample_rate().0,
+            format: config.sample_format().to_string(),
+        }
+    }
+}
+
+pub fn hosts() -> Vec<HostInfo> {
+    let default = cpal::default_host().id();
+    let mut hosts = Vec::new();
+
+    for id in cpal::available_hosts() {
+        let host = match cpal::host_from_id(id) {
+            Ok(host) => host,
+            Err(_) => continue,
+        };
+
+        hosts.push(HostInfo {
+            name: id.name().to_owned(),
+            default: id == default,
+            devices: devices(&host),
+        });
+    }
+
+    hosts
+}
+
+pub fn find(name: Option<&str>) -> Option<Device> {
+    let name = match name {
+        Some(name) => name,
+        None => return cpal::default_host().default_output_device(),
+    };
+    let (host, name) = match name.split_once(':') {
+        Some((host, device)) if is_host(host) => (Some(host), device),
+        _ => (None, name),
+    };
+    let default = cpal::default_host().id();
+    let mut ids = cpal::available_hosts();
+    ids.sort_by_key(|id| *id != default);
+
+    for id in ids {
+        if host.is_some_and(|host| !id.name().eq_ignore_ascii_case(host)) {
+            continue;
+        }
+
+        let host = match cpal::host_from_id(id) {
+            Ok(host) => host,
+            Err(_) => continue,
+        };
+        let Ok(mut devices) = host.output_devices() else {
+            continue;
+        };
+
+        if let Some(device) = devices.find(|device| device.name().is_ok_and(|n| n == name)) {
+            return Some(device);
+        }
+    }
+
+    None
+}
+
+fn is_host(name: &str) -> bool {
+    cpal::available_hosts()
+        .iter()
+        .any(|id| id.name().eq_ignore_ascii_case(name))
+}
+
+fn devices(host: &Host) -> Vec<DeviceInfo> {
+    let default = host
+        .default_output_device()
+        .and_then(|device| device.name().ok());
+    let Ok(devices) = host.output_devices() else {
+        return Vec::new();
+    };
+
+    devices
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let configs = device
+                .supported_output_configs()
+                .map(|configs| configs.map(ConfigInfo::from).collect())
+                .unwrap_or_default();
+
+            Some(DeviceInfo {
+                default: default.as_ref() == Some(&name),
+                name,
+                configs,
+            })
+        })
+        .collect()
+}
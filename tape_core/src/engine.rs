@@ -1,16 +1,26 @@
-use crate::buf::Spec;
+use crate::event::Event;
 use crate::io::Write;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{BuildStreamError, Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use crate::output::{Cpal, Output};
+use crate::pump::{Pump, Tap};
+use cpal::{BuildStreamError, PauseStreamError, PlayStreamError, StreamError};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
+
+const RETRIES: u32 = 5;
+const BACKOFF: Duration = Duration::from_millis(250);
 
 pub struct Engine<U> {
     provider: Arc<U>,
-    device: Device,
-    stream: Option<Stream>,
+    pump: Pump<U>,
+    output: Box<dyn Output<Tap>>,
     state: PlaybackState,
+    errors: Option<Sender<EngineError>>,
+    failure: Option<Failure>,
+    events: Option<Sender<Event>>,
 }
 
 impl<U> Engine<U> {
@@ -20,10 +30,30 @@ impl<U> Engine<U> {
 
     pub fn state(&mut self) -> PlaybackStateManager<'_> {
         PlaybackStateManager {
-            stream: self.stream.as_ref(),
+            output: self.output.as_mut(),
             state: &mut self.state,
+            events: self.events.as_ref(),
+        }
+    }
+
+    pub fn set_events(&mut self, events: Sender<Event>) {
+        self.events = Some(events);
+    }
+
+    /// Stream errors are reported here, to be handed back through `fail`.
+    pub fn set_errors(&mut self, errors: Sender<EngineError>) {
+        self.errors = Some(errors);
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(events) = self.events.as_ref() {
+            let _ = events.send(event);
         }
     }
+
+    pub fn device_name(&self) -> Option<String> {
+        self.output.name()
+    }
 }
 
 impl<U> Engine<U>
@@ -31,79 +61,148 @@ where
     Arc<U>: 'static + Write<Item = f32> + Send + Sync,
 {
     pub fn new(provider: U) -> Result<Self, EngineError> {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .ok_or(EngineError::Unsupported)?;
+        Self::with_output(provider, Box::new(Cpal::open(None)?))
+    }
+
+    pub fn with_device(provider: U, name: &str) -> Result<Self, EngineError> {
+        Self::with_output(provider, Box::new(Cpal::open(Some(name))?))
+    }
+
+    pub fn with_output(provider: U, output: Box<dyn Output<Tap>>) -> Result<Self, EngineError> {
         let provider = Arc::new(provider);
+        let pump = Pump::<U>::new(provider.clone()).map_err(EngineError::Decoder)?;
+
         let engine = Self {
+            pump,
             provider,
-            device,
-            stream: None,
+            output,
             state: PlaybackState::Paused,
+            errors: None,
+            failure: None,
+            events: None,
         };
 
         Ok(engine)
     }
 
-    pub fn _run<T>(&mut self, config: &StreamConfig) -> Result<(), EngineError>
+    pub fn run(&mut self) -> Result<(), EngineError> {
+        let tap = self.pump.tap().ok_or(EngineError::Busy)?;
+        let errors = match self.errors.as_ref() {
+            Some(errors) => errors.clone(),
+            None => std::sync::mpsc::channel().0,
+        };
+
+        self.output.start(tap, errors)
+    }
+
+    pub fn flush_with<F, R>(&mut self, f: F) -> R
     where
-        T: SizedSample + FromSample<f32>,
+        F: FnOnce(&Arc<U>) -> R,
     {
-        let mut provider = self.provider.clone();
-        let channels = config.channels as usize;
-        let stream = self
-            .device
-            .build_output_stream(
-                config,
-                move |buf: &mut [T], _: &_| {
-                    let frames = buf.len() / channels;
-                    let spec = Spec::new(frames, channels);
-                    let mut dst = crate::buf::proxy::int_mut(buf, spec);
-                    provider.write_all(&mut dst);
-                },
-                |_| {},
-                None,
-            )
-            .map_err(EngineError::ConnectionFailed)?;
-
-        debug!(
-            "Device configuration: channels: {}, sample rate: {}, sample format: {}",
-            config.channels,
-            config.sample_rate.0,
-            std::any::type_name::<T>()
-        );
+        self.pump.flush_with(f)
+    }
+
+    pub fn flush_if<F>(&mut self, f: F) -> bool
+    where
+        F: FnOnce(&Arc<U>) -> bool,
+    {
+        self.pump.flush_if(f)
+    }
+
+    pub fn set_device(&mut self, name: Option<&str>) -> Result<(), EngineError> {
+        self.set_output(Box::new(Cpal::open(name)?))
+    }
+
+    pub fn set_output(&mut self, output: Box<dyn Output<Tap>>) -> Result<(), EngineError> {
+        self.output.stop();
+        let prev = std::mem::replace(&mut self.output, output);
+
+        if let Err(e) = self.run() {
+            self.output = prev;
+            self.run()?;
+            self.resume();
+            return Err(e);
+        }
 
-        self.stream.replace(stream);
+        self.failure = None;
+        self.resume();
 
         Ok(())
     }
 
-    pub fn run(&mut self) -> Result<(), EngineError> {
-        let config = self
-            .device
-            .default_output_config()
-            .map_err(|_| EngineError::Unsupported)?;
-
-        match config.sample_format() {
-            SampleFormat::U8 => self._run::<u8>(&config.into()),
-            SampleFormat::U16 => self._run::<u16>(&config.into()),
-            SampleFormat::U32 => self._run::<u32>(&config.into()),
-            SampleFormat::U64 => self._run::<u64>(&config.into()),
-            SampleFormat::I8 => self._run::<i8>(&config.into()),
-            SampleFormat::I16 => self._run::<i16>(&config.into()),
-            SampleFormat::I32 => self._run::<i32>(&config.into()),
-            SampleFormat::I64 => self._run::<i64>(&config.into()),
-            SampleFormat::F32 => self._run::<f32>(&config.into()),
-            SampleFormat::F64 => self._run::<f64>(&config.into()),
-            _ => return Err(EngineError::Unsupported),
-        }?;
+    pub fn fail(&mut self, e: EngineError) {
+        warn!("Stream error: {}", e);
+        self.emit(Event::DeviceError {
+            message: e.to_string(),
+        });
+
+        if self.failure.is_none() {
+            self.output.stop();
+            self.failure = Some(Failure {
+                attempts: 0,
+                next: Instant::now(),
+            });
+        }
+    }
+
+    /// Time of the next attempt to reopen a failed stream.
+    pub fn retry_at(&self) -> Option<Instant> {
+        self.failure.as_ref().map(|failure| failure.next)
+    }
+
+    pub fn recover(&mut self) -> Result<(), EngineError> {
+        let failure = match self.failure.as_mut() {
+            Some(failure) if failure.next <= Instant::now() => failure,
+            _ => return Ok(()),
+        };
+
+        failure.attempts += 1;
+
+        let attempts = failure.attempts;
+
+        if attempts > RETRIES * 2 {
+            self.failure = None;
+            self.emit(Event::DeviceError {
+                message: EngineError::Lost.to_string(),
+            });
+            return Err(EngineError::Lost);
+        }
+
+        failure.next = Instant::now() + BACKOFF * 2u32.pow((attempts - 1) % RETRIES);
+
+        if let Err(e) = self
+            .output
+            .reopen(attempts > RETRIES)
+            .and_then(|_| self.run())
+        {
+            debug!("Failed to recover stream (attempt {}): {}", attempts, e);
+            return Ok(());
+        }
+
+        info!(
+            "Recovered stream on {}",
+            self.device_name().as_deref().unwrap_or("unknown device")
+        );
+        self.failure = None;
+        self.resume();
 
         Ok(())
     }
+
+    fn resume(&mut self) {
+        let state = self.state.get();
+        self.state().set(state);
+    }
+}
+
+struct Failure {
+    attempts: u32,
+    next: Instant,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum PlaybackState {
     Paused,
     Playing,
@@ -120,8 +219,9 @@ impl PlaybackState {
 }
 
 pub struct PlaybackStateManager<'a> {
-    stream: Option<&'a Stream>,
+    output: &'a mut dyn Output<Tap>,
     state: &'a mut PlaybackState,
+    events: Option<&'a Sender<Event>>,
 }
 
 impl<'a> PlaybackStateManager<'a> {
@@ -130,22 +230,20 @@ impl<'a> PlaybackStateManager<'a> {
     }
 
     pub fn set(&mut self, state: PlaybackState) {
-        if let Some(stream) = self.stream {
-            match &state {
-                PlaybackState::Paused => {
-                    if let Err(e) = stream.pause() {
-                        warn!("{:#}", e);
-                    }
-                }
-                PlaybackState::Playing => {
-                    if let Err(e) = stream.play() {
-                        warn!("{:#}", e);
-                    }
-                }
-            };
-
-            self.state.set(state);
+        let result = match &state {
+            PlaybackState::Paused => self.output.pause(),
+            PlaybackState::Playing => self.output.play(),
+        };
+
+        if let Err(e) = result {
+            warn!("{:#}", e);
+        }
+
+        if let Some(events) = self.events.filter(|_| *self.state != state) {
+            let _ = events.send(Event::StateChanged { state });
         }
+
+        self.state.set(state);
     }
 }
 
@@ -153,6 +251,20 @@ impl<'a> PlaybackStateManager<'a> {
 pub enum EngineError {
     #[error("unsupported device")]
     Unsupported,
+    #[error("{0}: no such device")]
+    NotFound(String),
+    #[error("lost connection to device")]
+    Lost,
+    #[error("decoder output is still in use")]
+    Busy,
+    #[error("failed to spawn decoder thread: {0}")]
+    Decoder(std::io::Error),
     #[error(transparent)]
     ConnectionFailed(#[from] BuildStreamError),
+    #[error(transparent)]
+    Stream(#[from] StreamError),
+    #[error(transparent)]
+    Play(#[from] PlayStreamError),
+    #[error(transparent)]
+    Pause(#[from] PauseStreamError),
 }
@@ -0,0 +1,214 @@
+use crate::buf::{BufMut, Spec};
+use crate::gain::{Normalize, ReplayGain};
+use crate::io::{Release, Seek, SeekFrom, Tell, Write};
+use crate::meta::{Describe, Metadata};
+use crate::resample::{Quality, Resampler};
+use crate::sound::{Sound, SoundError};
+use cpal::{FromSample, Sample};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::warn;
+
+static EMPTY: LazyLock<Metadata> = LazyLock::new(Metadata::default);
+
+pub struct Entry {
+    source: Arc<Source>,
+    quality: Quality,
+    sound: Option<Resampler<Sound>>,
+}
+
+impl Entry {
+    pub fn new<P>(path: P, quality: Quality) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            source: Arc::new(Source {
+                path: path.into(),
+                info: OnceLock::new(),
+            }),
+            quality,
+            sound: None,
+        }
+    }
+
+    pub fn source(&self) -> Arc<Source> {
+        self.source.clone()
+    }
+
+    pub fn path(&self) -> &Path {
+        self.source.path()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.sound.is_some()
+    }
+
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.quality = quality;
+
+        if let Some(sound) = self.sound.as_mut() {
+            sound.set_quality(quality);
+        }
+    }
+
+    fn load(&mut self) -> Option<&mut Resampler<Sound>> {
+        if self.sound.is_none() && self.source.is_valid() != Some(false) {
+            match self.source.open() {
+                Ok(sound) => self.sound = Some(Resampler::new(sound, self.quality)),
+                Err(e) => warn!("{}: {}", self.path().display(), e),
+            }
+        }
+
+        self.sound.as_mut()
+    }
+
+    fn info(&self) -> Option<&Info> {
+        self.source.info.get()?.as_ref()
+    }
+}
+
+impl Write for Entry {
+    type Item = f32;
+
+    fn write<U>(&mut self, dst: &mut U) -> usize
+    where
+        U: BufMut,
+        U::Item: Sample + FromSample<Self::Item>,
+    {
+        match self.load() {
+            Some(sound) => sound.write(dst),
+            None => 0,
+        }
+    }
+
+    fn format(&self) -> Option<Spec> {
+        self.sound.as_ref()?.format()
+    }
+}
+
+impl Seek for Entry {
+    fn seek(&mut self, pos: SeekFrom) -> bool {
+        let fresh = self.sound.is_none();
+
+        match self.load() {
+            // A freshly opened sound already sits at the start.
+            Some(_) if fresh && matches!(pos, SeekFrom::Start(Duration::ZERO)) => true,
+            Some(sound) => sound.seek(pos),
+            None => false,
+        }
+    }
+}
+
+impl Tell for Entry {
+    fn position(&self) -> Duration {
+        match self.sound.as_ref() {
+            Some(sound) => sound.position(),
+            None => Duration::ZERO,
+        }
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        match self.sound.as_ref() {
+            Some(sound) => sound.duration(),
+            None => self.info()?.duration,
+        }
+    }
+}
+
+impl Release for Entry {
+    fn release(&mut self) {
+        self.sound.take();
+    }
+}
+
+impl Describe for Entry {
+    fn metadata(&self) -> &Metadata {
+        if let Some(sound) = self.sound.as_ref() {
+            return sound.metadata();
+        }
+
+        match self.info() {
+            Some(info) => &info.metadata,
+            None => &EMPTY,
+        }
+    }
+}
+
+impl Normalize for Entry {
+    fn replay_gain(&self) -> ReplayGain {
+        match self.info() {
+            Some(info) => info.replay_gain,
+            None => ReplayGain::default(),
+        }
+    }
+}
+
+pub struct Source {
+    path: PathBuf,
+    info: OnceLock<Option<Info>>,
+}
+
+impl Source {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_valid(&self) -> Option<bool> {
+        self.info.get().map(Option::is_some)
+    }
+
+    pub fn probe(&self) -> Result<(), EntryError> {
+        match self.is_valid() {
+            Some(true) => Ok(()),
+            Some(false) => Err(EntryError::Invalid),
+            None => self.open().map(drop),
+        }
+    }
+
+    fn open(&self) -> Result<Sound, EntryError> {
+        let sound = File::open(&self.path)
+            .map_err(EntryError::from)
+            .and_then(|file| Ok(Sound::new(file)?));
+        let sound = match sound {
+            Ok(sound) => sound,
+            Err(e) => {
+                // Only a file that was read and rejected stays invalid, I/O errors may pass.
+                if !matches!(e, EntryError::Io(_) | EntryError::Sound(SoundError::Io(_))) {
+                    self.info.get_or_init(|| None);
+                }
+
+                return Err(e);
+            }
+        };
+
+        self.info.get_or_init(|| {
+            Some(Info {
+                metadata: sound.metadata().without_cover(),
+                duration: sound.duration(),
+                replay_gain: sound.replay_gain(),
+            })
+        });
+
+        Ok(sound)
+    }
+}
+
+struct Info {
+    metadata: Metadata,
+    duration: Option<Duration>,
+    replay_gain: ReplayGain,
+}
+
+#[derive(Error, Debug)]
+pub enum EntryError {
+    #[error("invalid media file")]
+    Invalid,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Sound(#[from] SoundError),
+}
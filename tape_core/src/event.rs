@@ -0,0 +1,18 @@
+use crate::engine::PlaybackState;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "event", rename_all = "kebab-case"))]
+pub enum Event {
+    TrackChanged { index: usize },
+    StateChanged { state: PlaybackState },
+    Seeked { position: Duration },
+    QueueChanged,
+    ConfigChanged,
+    EndOfQueue,
+    DeviceError { message: String },
+    ProbeFailed { path: PathBuf, message: String },
+}
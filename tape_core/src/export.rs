@@ -0,0 +1,148 @@
+use crate::buf::{Buf, Layout, Seq, Spec};
+use crate::io::Write;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub mod flac;
+pub mod wav;
+
+pub use flac::Flac;
+pub use wav::Wav;
+
+const PERIOD: usize = 4096;
+
+pub trait Encode {
+    fn encode<T>(&mut self, src: &T) -> std::io::Result<()>
+    where
+        T: Buf<Item = f32>;
+
+    fn finish(&mut self) -> std::io::Result<()>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Container {
+    Wav,
+    Flac,
+}
+
+impl Container {
+    pub fn from_path<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let extension = path.as_ref().extension()?.to_str()?;
+
+        match extension.to_ascii_lowercase().as_str() {
+            "wav" | "wave" => Some(Self::Wav),
+            "flac" => Some(Self::Flac),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SampleFormat {
+    S16,
+    S24,
+    S32,
+    F32,
+}
+
+impl SampleFormat {
+    pub fn bits(&self) -> u32 {
+        match self {
+            Self::S16 => 16,
+            Self::S24 => 24,
+            Self::S32 | Self::F32 => 32,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::F32)
+    }
+}
+
+impl FromStr for SampleFormat {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "s16" => Ok(Self::S16),
+            "s24" => Ok(Self::S24),
+            "s32" => Ok(Self::S32),
+            "f32" => Ok(Self::F32),
+            _ => Err(ExportError::Invalid(s.into())),
+        }
+    }
+}
+
+pub fn render<T, E>(
+    provider: &mut T,
+    encoder: &mut E,
+    layout: Layout,
+    rate: u32,
+    limit: Option<u64>,
+) -> Result<u64, ExportError>
+where
+    T: Write<Item = f32>,
+    E: Encode,
+{
+    let mut buf = Seq::<f32>::with_spec(Spec::new(PERIOD, layout, rate));
+    let mut total = 0;
+
+    loop {
+        let want = match limit {
+            Some(limit) => std::cmp::min(limit - total, PERIOD as u64) as usize,
+            None => PERIOD,
+        };
+
+        if want == 0 {
+            break;
+        }
+
+        buf.set_pos(0);
+        buf.set_len(0);
+
+        let mut done = false;
+
+        while buf.len() < want && !done {
+            done = provider.write(&mut buf) == 0;
+        }
+
+        let n = std::cmp::min(buf.len(), want);
+        buf.set_len(n);
+        encoder.encode(&buf)?;
+        total += n as u64;
+
+        if done {
+            break;
+        }
+    }
+
+    encoder.finish()?;
+
+    Ok(total)
+}
+
+fn quantize(sample: f32, bits: u32) -> i32 {
+    let scale = (1i64 << (bits - 1)) as f64;
+    let value = (sample as f64 * scale).round();
+
+    value.clamp(-scale, scale - 1.0) as i32
+}
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("{0}: invalid sample format")]
+    Invalid(String),
+    #[error("sample format not supported by container")]
+    Unsupported,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,355 @@
+use super::{quantize, Encode, ExportError, SampleFormat};
+use crate::buf::{Buf, Layout};
+use std::io::{Seek, SeekFrom, Write};
+
+const BLOCK: usize = 4096;
+const MAX_ORDER: usize = 4;
+const MAX_RICE: u32 = 14;
+
+pub struct Flac<W> {
+    inner: W,
+    channels: usize,
+    bits: u32,
+    rate: u32,
+    block: Vec<Vec<i32>>,
+    frame: u64,
+    total: u64,
+    residual: Vec<i32>,
+}
+
+impl<W> Flac<W>
+where
+    W: Write + Seek,
+{
+    pub fn new(
+        inner: W,
+        format: SampleFormat,
+        layout: Layout,
+        rate: u32,
+    ) -> Result<Self, ExportError> {
+        let channels = layout.count();
+
+        if !matches!(format, SampleFormat::S16 | SampleFormat::S24)
+            || !(1..=8).contains(&channels)
+            || !(1..1 << 20).contains(&rate)
+        {
+            return Err(ExportError::Unsupported);
+        }
+
+        let mut flac = Self {
+            inner,
+            channels,
+            bits: format.bits(),
+            rate,
+            block: vec![Vec::with_capacity(BLOCK); channels],
+            frame: 0,
+            total: 0,
+            residual: Vec::with_capacity(BLOCK),
+        };
+
+        flac.inner.write_all(b"fLaC")?;
+        flac.write_stream_info()?;
+
+        Ok(flac)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_stream_info(&mut self) -> std::io::Result<()> {
+        let mut bits = BitWriter::default();
+        bits.push(1, 1);
+        bits.push(0, 7);
+        bits.push(34, 24);
+        bits.push(BLOCK as u64, 16);
+        bits.push(BLOCK as u64, 16);
+        bits.push(0, 24);
+        bits.push(0, 24);
+        bits.push(self.rate as u64, 20);
+        bits.push(self.channels as u64 - 1, 3);
+        bits.push(self.bits as u64 - 1, 5);
+        bits.push(self.total, 36);
+        bits.push(0, 64);
+        bits.push(0, 64);
+
+        self.inner.write_all(&bits.finish())
+    }
+
+    fn write_frame(&mut self) -> std::io::Result<()> {
+        let len = self.block[0].len();
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        let mut bits = BitWriter::default();
+        bits.push(0xfff8, 16);
+        bits.push(0b0111, 4);
+        bits.push(0b0000, 4);
+        bits.push(self.channels as u64 - 1, 4);
+        bits.push(0b000, 3);
+        bits.push(0, 1);
+        push_utf8(&mut bits, self.frame);
+        bits.push(len as u64 - 1, 16);
+
+        let crc = crc8(bits.bytes());
+        bits.push(crc as u64, 8);
+
+        for channel in 0..self.channels {
+            let samples = std::mem::take(&mut self.block[channel]);
+            self.write_subframe(&mut bits, &samples);
+            self.block[channel] = samples;
+            self.block[channel].clear();
+        }
+
+        bits.align();
+
+        let crc = crc16(bits.bytes());
+        bits.push(crc as u64, 16);
+
+        self.inner.write_all(&bits.finish())?;
+        self.frame += 1;
+        self.total += len as u64;
+
+        Ok(())
+    }
+
+    fn write_subframe(&mut self, bits: &mut BitWriter, samples: &[i32]) {
+        if samples.iter().all(|sample| *sample == samples[0]) {
+            bits.push(0b0000_0000, 8);
+            bits.push_signed(samples[0], self.bits);
+            return;
+        }
+
+        let mut best = None;
+
+        for order in 0..=std::cmp::min(MAX_ORDER, samples.len() - 1) {
+            predict(samples, order, &mut self.residual);
+            let (k, cost) = rice_parameter(&self.residual);
+
+            if best.is_none_or(|(_, _, best)| cost < best) {
+                best = Some((order, k, cost));
+            }
+        }
+
+        let verbatim = samples.len() as u64 * self.bits as u64;
+
+        match best {
+            Some((order, k, cost)) if cost < verbatim => {
+                predict(samples, order, &mut self.residual);
+                bits.push(0b0000_1000 | order as u64, 7);
+                bits.push(0, 1);
+
+                for sample in &samples[..order] {
+                    bits.push_signed(*sample, self.bits);
+                }
+
+                bits.push(0b00, 2);
+                bits.push(0b0000, 4);
+                bits.push(k as u64, 4);
+
+                for residual in &self.residual {
+                    let u = zigzag(*residual);
+                    bits.push_unary(u >> k);
+                    bits.push(u & mask(k), k);
+                }
+            }
+            _ => {
+                bits.push(0b0000_0010, 8);
+
+                for sample in samples {
+                    bits.push_signed(*sample, self.bits);
+                }
+            }
+        }
+    }
+}
+
+impl<W> Encode for Flac<W>
+where
+    W: Write + Seek,
+{
+    fn encode<T>(&mut self, src: &T) -> std::io::Result<()>
+    where
+        T: Buf<Item = f32>,
+    {
+        for frame in src.frames().skip(src.pos()).take(src.len() - src.pos()) {
+            for (channel, sample) in frame.iter().enumerate() {
+                self.block[channel].push(quantize(*sample, self.bits));
+            }
+
+            if self.block[0].len() == BLOCK {
+                self.write_frame()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.write_frame()?;
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.write_stream_info()?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    len: u32,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u64, bits: u32) {
+        let mut bits = bits;
+
+        while bits > 0 {
+            let n = std::cmp::min(bits, 32);
+            bits -= n;
+            self.acc = self.acc << n | (value >> bits) & mask(n);
+            self.len += n;
+
+            while self.len >= 8 {
+                self.len -= 8;
+                self.bytes.push((self.acc >> self.len) as u8);
+            }
+
+            self.acc &= mask(self.len);
+        }
+    }
+
+    fn push_signed(&mut self, value: i32, bits: u32) {
+        self.push(value as u64 & mask(bits), bits);
+    }
+
+    fn push_unary(&mut self, n: u64) {
+        let mut n = n;
+
+        while n > 32 {
+            self.push(0, 32);
+            n -= 32;
+        }
+
+        self.push(1, n as u32 + 1);
+    }
+
+    fn align(&mut self) {
+        if self.len > 0 {
+            self.push(0, 8 - self.len);
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    match bits {
+        64 => u64::MAX,
+        bits => (1 << bits) - 1,
+    }
+}
+
+fn push_utf8(bits: &mut BitWriter, n: u64) {
+    if n < 0x80 {
+        bits.push(n, 8);
+        return;
+    }
+
+    let mut len = 2;
+
+    while len < 7 && n >= 1 << (5 * len + 1) {
+        len += 1;
+    }
+
+    let lead = (0xff00u64 >> len) & 0xff;
+    bits.push(lead | n >> (6 * (len - 1)), 8);
+
+    for i in (0..len - 1).rev() {
+        bits.push(0x80 | (n >> (6 * i)) & 0x3f, 8);
+    }
+}
+
+fn predict(samples: &[i32], order: usize, dst: &mut Vec<i32>) {
+    dst.clear();
+
+    for i in order..samples.len() {
+        let s = |j: usize| samples[i - j] as i64;
+        let residual = match order {
+            0 => s(0),
+            1 => s(0) - s(1),
+            2 => s(0) - 2 * s(1) + s(2),
+            3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+            _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+        };
+
+        dst.push(residual.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+    }
+}
+
+fn rice_parameter(residual: &[i32]) -> (u32, u64) {
+    let mut best = (0, u64::MAX);
+
+    for k in 0..=MAX_RICE {
+        let cost = residual
+            .iter()
+            .map(|r| (zigzag(*r) >> k) + 1 + k as u64)
+            .sum::<u64>();
+
+        if cost < best.1 {
+            best = (k, cost);
+        }
+    }
+
+    best
+}
+
+fn zigzag(n: i32) -> u64 {
+    ((n << 1) ^ (n >> 31)) as u32 as u64
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+
+    for byte in bytes {
+        crc ^= byte;
+
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                crc << 1 ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0u16;
+
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
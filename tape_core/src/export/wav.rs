@@ -0,0 +1,139 @@
+use super::{quantize, Encode, ExportError, SampleFormat};
+use crate::buf::{Buf, Layout};
+use std::io::{Seek, SeekFrom, Write};
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+const KSDATAFORMAT_SUBTYPE_PCM: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+const KSDATAFORMAT_SUBTYPE_IEEE_FLOAT: [u8; 16] = [
+    0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+pub struct Wav<W> {
+    inner: W,
+    format: SampleFormat,
+    data: u64,
+    len: u64,
+    scratch: Vec<u8>,
+}
+
+impl<W> Wav<W>
+where
+    W: Write + Seek,
+{
+    pub fn new(
+        inner: W,
+        format: SampleFormat,
+        layout: Layout,
+        rate: u32,
+    ) -> Result<Self, ExportError> {
+        let mut wav = Self {
+            inner,
+            format,
+            data: 0,
+            len: 0,
+            scratch: Vec::new(),
+        };
+
+        wav.write_header(layout, rate)?;
+
+        Ok(wav)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_header(&mut self, layout: Layout, rate: u32) -> std::io::Result<()> {
+        let channels = layout.count() as u16;
+        let bits = self.format.bits() as u16;
+        let align = channels * bits / 8;
+        let extensible = channels > 2 || bits > 16 && !self.format.is_float();
+        let tag = match (extensible, self.format.is_float()) {
+            (true, _) => WAVE_FORMAT_EXTENSIBLE,
+            (false, true) => WAVE_FORMAT_IEEE_FLOAT,
+            (false, false) => WAVE_FORMAT_PCM,
+        };
+        let mut fmt = Vec::with_capacity(40);
+        fmt.extend_from_slice(&tag.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&rate.to_le_bytes());
+        fmt.extend_from_slice(&(rate * align as u32).to_le_bytes());
+        fmt.extend_from_slice(&align.to_le_bytes());
+        fmt.extend_from_slice(&bits.to_le_bytes());
+
+        if extensible {
+            let subtype = match self.format.is_float() {
+                true => KSDATAFORMAT_SUBTYPE_IEEE_FLOAT,
+                false => KSDATAFORMAT_SUBTYPE_PCM,
+            };
+
+            fmt.extend_from_slice(&22u16.to_le_bytes());
+            fmt.extend_from_slice(&bits.to_le_bytes());
+            fmt.extend_from_slice(&layout.bits().to_le_bytes());
+            fmt.extend_from_slice(&subtype);
+        }
+
+        self.inner.write_all(b"RIFF")?;
+        self.inner.write_all(&0u32.to_le_bytes())?;
+        self.inner.write_all(b"WAVE")?;
+        self.inner.write_all(b"fmt ")?;
+        self.inner.write_all(&(fmt.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&fmt)?;
+        self.inner.write_all(b"data")?;
+        self.inner.write_all(&0u32.to_le_bytes())?;
+        self.data = self.inner.stream_position()?;
+
+        Ok(())
+    }
+}
+
+impl<W> Encode for Wav<W>
+where
+    W: Write + Seek,
+{
+    fn encode<T>(&mut self, src: &T) -> std::io::Result<()>
+    where
+        T: Buf<Item = f32>,
+    {
+        let bits = self.format.bits();
+        self.scratch.clear();
+
+        for frame in src.frames().skip(src.pos()).take(src.len() - src.pos()) {
+            for sample in frame.iter() {
+                match self.format {
+                    SampleFormat::F32 => self.scratch.extend_from_slice(&sample.to_le_bytes()),
+                    _ => {
+                        let bytes = quantize(*sample, bits).to_le_bytes();
+                        self.scratch.extend_from_slice(&bytes[..bits as usize / 8]);
+                    }
+                }
+            }
+        }
+
+        self.inner.write_all(&self.scratch)?;
+        self.len += self.scratch.len() as u64;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        let len = u32::try_from(self.len).unwrap_or(u32::MAX);
+
+        if self.len % 2 == 1 {
+            self.inner.write_all(&[0])?;
+        }
+
+        let riff = (self.data - 8 + self.len + self.len % 2).min(u32::MAX as u64) as u32;
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.inner.write_all(&riff.to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(self.data - 4))?;
+        self.inner.write_all(&len.to_le_bytes())?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()
+    }
+}
@@ -1,15 +1,34 @@
-use crate::buf::BufMut;
-use crate::io::{Seek, Write};
+use crate::buf::{Buf, BufMut, Seq, Spec};
+use crate::event::Event;
+use crate::gain::{Level, Normalize, ReplayGainMode, Volume};
+use crate::io::{Release, Seek, SeekFrom, Tell, Write};
+use crate::meta::Describe;
+use crate::mix::Curve;
+use crate::resample::Quality;
+use crate::shuffle::{Order, Shuffle};
 use cpal::{FromSample, Sample};
 use parking_lot::{Mutex, MutexGuard};
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::time::Duration;
+
+const PRELOAD: Duration = Duration::from_secs(5);
 
 pub struct Factory<T> {
     items: Mutex<Vec<T>>,
     state: Mutex<FactoryState>,
     pos: AtomicUsize,
+    track: AtomicUsize,
+    preloaded: Mutex<Option<usize>>,
+    order: Mutex<Order>,
+    fade: Mutex<Option<Fade>>,
+    bufs: Mutex<Option<(Seq<f32>, Seq<f32>)>>,
+    latency: Mutex<Duration>,
+    ended: AtomicBool,
+    events: Mutex<Option<Sender<Event>>>,
 }
 
 impl<T> Factory<T> {
@@ -18,6 +37,14 @@ impl<T> Factory<T> {
             items: Mutex::new(Vec::new()),
             state: Mutex::new(FactoryState::default()),
             pos: AtomicUsize::new(0),
+            track: AtomicUsize::new(0),
+            preloaded: Mutex::new(None),
+            order: Mutex::new(Order::new()),
+            fade: Mutex::new(None),
+            bufs: Mutex::new(None),
+            latency: Mutex::new(Duration::ZERO),
+            ended: AtomicBool::new(false),
+            events: Mutex::new(None),
         }
     }
 
@@ -25,13 +52,6 @@ impl<T> Factory<T> {
         self.items.lock()
     }
 
-    pub fn map<F>(&self, f: F)
-    where
-        F: FnOnce(&mut Vec<T>),
-    {
-        f(&mut *self.items())
-    }
-
     pub fn state(&self) -> MutexGuard<FactoryState> {
         self.state.lock()
     }
@@ -39,44 +59,422 @@ impl<T> Factory<T> {
     pub fn pos(&self) -> usize {
         self.pos.load(Ordering::SeqCst)
     }
+
+    pub fn latency(&self) -> Duration {
+        *self.latency.lock()
+    }
+
+    pub fn set_events(&self, events: Sender<Event>) {
+        self.events.lock().replace(events);
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(events) = self.events.lock().as_ref() {
+            let _ = events.send(event);
+        }
+    }
+
+    pub fn inspect<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[T]) -> R,
+    {
+        f(&self.items())
+    }
 }
 
 impl<T> Factory<T>
 where
-    T: Seek,
+    T: Release,
 {
-    pub fn seek(&self, ts: u64) {
-        let flag = {
-            let pos = self.pos();
+    pub fn map<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Vec<T>),
+    {
+        let pending = self.settle();
+        let mut items = self.items();
+        release(&mut items, &pending, self.pos());
+        f(&mut items)
+    }
+
+    fn settle(&self) -> [Option<usize>; 2] {
+        [
+            self.fade.lock().take().map(|fade| fade.next),
+            self.preloaded.lock().take(),
+        ]
+    }
+}
+
+impl<T> Factory<T>
+where
+    T: Tell,
+{
+    pub fn position(&self) -> Option<Duration> {
+        let latency = self.latency();
+        let position = self.items().get(self.pos())?.position();
+
+        Some(position.saturating_sub(latency))
+    }
 
-            match self.items().get_mut(pos) {
-                Some(item) => item.seek(ts),
-                None => return,
+    pub fn seek(&self, pos: SeekFrom) -> bool
+    where
+        T: Seek + Release + Describe,
+    {
+        if let Some(fade) = self.fade.lock().take() {
+            release(&mut self.items(), &[Some(fade.next)], self.pos());
+        }
+
+        let pos = match pos {
+            SeekFrom::Forward(_) | SeekFrom::Backward(_) => {
+                match self
+                    .position()
+                    .and_then(|t| pos.resolve(t, self.duration()))
+                {
+                    Some(t) => SeekFrom::Start(t),
+                    None => return false,
+                }
+            }
+            pos => pos,
+        };
+        let (flag, position) = {
+            let current = self.pos();
+
+            match self.items().get_mut(current) {
+                Some(item) => (item.seek(pos), item.position()),
+                None => return false,
             }
         };
 
         if flag {
+            self.emit(Event::Seeked { position });
+        } else {
             self.translate(1, TranslateBehavior::Modal);
         }
+
+        flag
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.items().get(self.pos())?.duration()
     }
+}
 
+impl<T> Factory<T>
+where
+    T: Seek + Tell + Release + Describe,
+{
     pub fn select(&self, pos: usize) -> bool {
-        if let Some(item) = self.items().get_mut(pos) {
+        let pending = self.settle();
+        let mut items = self.items();
+        let current = self.pos();
+        release(&mut items, &pending, current);
+
+        if let Some(item) = items.get_mut(pos) {
             if !item.rewind() {
                 return false;
             }
 
             self.pos.store(pos, Ordering::SeqCst);
+            self.track.fetch_add(1, Ordering::SeqCst);
+            release(&mut items, &[Some(current)], pos);
+            drop(items);
 
+            self.emit(Event::TrackChanged { index: pos });
             return true;
         }
 
         false
     }
 
+    pub fn extend<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.splice(usize::MAX, iter, false);
+    }
+
+    pub fn insert<I>(&self, index: usize, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.splice(index, iter, false);
+    }
+
+    pub fn play_next<I>(&self, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.splice(self.pos().saturating_add(1), iter, true);
+    }
+
+    fn splice<I>(&self, index: usize, iter: I, next: bool)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let preloaded = self.preloaded.lock().take();
+        let mut order = self.order.lock();
+        let (len, start, end) = {
+            let mut items = self.items();
+            release(&mut items, &[preloaded], self.pos());
+
+            let len = items.len();
+            let start = std::cmp::min(index, len);
+            items.splice(start..start, iter);
+            (len, start, start + items.len() - len)
+        };
+        let synced = order.mode() != Shuffle::Off && order.len() == len;
+
+        self.remap(&mut order, |i| match i {
+            i if i >= start && i < len => Some(i + end - start),
+            i => Some(i),
+        });
+        self.emit(Event::QueueChanged);
+
+        if !synced || start == end {
+            return;
+        }
+
+        let groups = self.groups(order.mode(), start..end);
+        let after = order.rank(self.pos());
+
+        // Entries queued to play next go right after the current one, others join the remainder
+        // of the current cycle.
+        if next {
+            order.place(groups, after.map_or(0, |rank| rank + 1));
+        } else {
+            order.insert(groups, after);
+        }
+    }
+
+    pub fn remove(&self, ranges: &[Range<usize>]) -> bool {
+        let repeat = matches!(self.state().repeat_mode(), RepeatMode::Playlist);
+        let pending = self.settle();
+        let mut order = self.order.lock();
+        let mut items = self.items();
+        let pos = self.pos();
+        release(&mut items, &pending, pos);
+
+        let len = items.len();
+        let mut map = Vec::with_capacity(len);
+        let mut n = 0;
+
+        for i in 0..len {
+            if ranges.iter().any(|range| range.contains(&i)) {
+                map.push(None);
+            } else {
+                map.push(Some(n));
+                n += 1;
+            }
+        }
+
+        let removed = map.get(pos).is_some_and(Option::is_none);
+        let synced = order.len() == len;
+
+        // A removed entry hands over to the one that would have played after it, which wraps
+        // around to the start when the playlist repeats.
+        let successor = match order.rank(pos) {
+            Some(rank) if synced && order.mode() != Shuffle::Off => {
+                let wrap = if repeat { rank } else { 0 };
+                order
+                    .iter()
+                    .skip(rank)
+                    .chain(order.iter().take(wrap))
+                    .find_map(|i| map[i])
+            }
+            _ => {
+                let wrap = if repeat { pos } else { 0 };
+                map.iter()
+                    .skip(pos)
+                    .chain(map.iter().take(wrap))
+                    .find_map(|i| *i)
+            }
+        };
+
+        let mut i = 0;
+        items.retain(|_| {
+            i += 1;
+            map[i - 1].is_some()
+        });
+
+        let pos = match map.get(pos) {
+            Some(Some(pos)) => *pos,
+            Some(None) => successor.unwrap_or(n),
+            None => n,
+        };
+
+        if removed {
+            if let Some(item) = items.get_mut(pos) {
+                item.rewind();
+            }
+        }
+
+        drop(items);
+        self.remap(&mut order, |i| map.get(i).copied().flatten());
+        self.pos.store(pos, Ordering::SeqCst);
+
+        if removed {
+            self.track.fetch_add(1, Ordering::SeqCst);
+        }
+
+        if !synced {
+            order.clear();
+        }
+
+        self.emit(Event::QueueChanged);
+
+        if removed {
+            self.emit(Event::TrackChanged { index: pos });
+        }
+
+        removed
+    }
+
+    pub fn reorder(&self, range: Range<usize>, to: usize) {
+        let preloaded = self.preloaded.lock().take();
+        let mut order = self.order.lock();
+        let mut items = self.items();
+        release(&mut items, &[preloaded], self.pos());
+
+        let len = items.len();
+        let range = std::cmp::min(range.start, len)..std::cmp::min(range.end, len);
+        let mut indices = (0..len).filter(|i| !range.contains(i)).collect::<Vec<_>>();
+        let to = std::cmp::min(to, indices.len());
+        indices.splice(to..to, range);
+
+        let mut map = vec![0; len];
+        let mut src = items.drain(..).map(Some).collect::<Vec<_>>();
+
+        for (n, i) in indices.into_iter().enumerate() {
+            map[i] = n;
+            items.extend(src[i].take());
+        }
+
+        drop(items);
+        self.remap(&mut order, |i| map.get(i).copied().or(Some(i)));
+        self.emit(Event::QueueChanged);
+    }
+
+    /// Album groups rely on metadata, which lazy entries only have once probed.
+    pub fn regroup(&self) {
+        if self.order.lock().mode() != Shuffle::Album {
+            return;
+        }
+
+        let preloaded = self.preloaded.lock().take();
+        let mut order = self.order.lock();
+        let mut items = self.items();
+        let pos = self.pos();
+        let len = items.len();
+        release(&mut items, &[preloaded], pos);
+        drop(items);
+
+        if order.mode() == Shuffle::Album && order.len() == len {
+            let groups = self.groups(Shuffle::Album, 0..len);
+            order.regroup(groups, (pos < len).then_some(pos));
+        }
+    }
+
+    pub fn clear(&self) {
+        self.settle();
+        self.order.lock().clear();
+        self.items().clear();
+        self.pos.store(0, Ordering::SeqCst);
+        self.emit(Event::QueueChanged);
+    }
+
+    fn remap<F>(&self, order: &mut Order, f: F)
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let mut fade = self.fade.lock();
+
+        if let Some(pos) = f(self.pos()) {
+            self.pos.store(pos, Ordering::SeqCst);
+        }
+
+        if let Some(next) = fade.as_ref().map(|fade| f(fade.next)) {
+            match (fade.as_mut(), next) {
+                (Some(fade), Some(next)) => fade.next = next,
+                _ => *fade = None,
+            }
+        }
+
+        drop(fade);
+        order.remap(f);
+    }
+
     pub fn translate(&self, delta: isize, behavior: TranslateBehavior) -> bool {
+        match self.target(delta, behavior) {
+            Some(pos) => self.select(pos),
+            None => false,
+        }
+    }
+
+    fn preload(&self) {
+        let mut preloaded = self.preloaded.lock();
+
+        if preloaded.is_some() {
+            return;
+        }
+
+        let pos = self.pos();
+        let next = match self.target(1, TranslateBehavior::Modal) {
+            Some(next) if next != pos => next,
+            _ => return,
+        };
+        let mut items = self.items();
+
+        if items
+            .get(pos)
+            .and_then(|item| item.remaining())
+            .is_some_and(|remaining| remaining > PRELOAD)
+        {
+            return;
+        }
+
+        // Rewinding decodes the first packet, so the transition itself does no I/O.
+        if items[next].rewind() {
+            preloaded.replace(next);
+        }
+    }
+
+    fn advance(&self, from: usize) -> bool {
+        // Commands no longer wait for the decoder, so one may have moved on in the meantime.
+        if self.pos() != from {
+            return true;
+        }
+
+        let preloaded = self.preloaded.lock().take();
+
+        match self.target(1, TranslateBehavior::Modal) {
+            Some(next) if preloaded == Some(next) => {
+                self.shift(next);
+                true
+            }
+            Some(next) => {
+                // Step over entries that fail to open so playback keeps going.
+                if !self.select(next) {
+                    self.shift(next);
+                }
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn shift(&self, pos: usize) {
+        let mut items = self.items();
+        let prev = self.pos.swap(pos, Ordering::SeqCst);
+        self.track.fetch_add(1, Ordering::SeqCst);
+        release(&mut items, &[Some(prev)], pos);
+        drop(items);
+
+        self.emit(Event::TrackChanged { index: pos });
+    }
+
+    fn target(&self, delta: isize, behavior: TranslateBehavior) -> Option<usize> {
         if self.items().is_empty() {
-            return false;
+            return None;
         }
 
         let repeat_mode = match behavior {
@@ -84,17 +482,75 @@ where
             TranslateBehavior::Modal if self.can_translate(delta) => {
                 self.state().repeat_mode().get()
             }
-            _ => return false,
+            _ => return None,
         };
         let len = self.items().len();
         let pos = self.pos();
+        let shuffle = self.state().shuffle().get();
+
+        if shuffle != Shuffle::Off && !matches!(repeat_mode, RepeatMode::Track) {
+            return self.shuffled(delta, repeat_mode, shuffle);
+        }
+
         let pos = match repeat_mode {
             RepeatMode::Disabled => pos.saturating_add_signed(delta),
             RepeatMode::Track => pos,
             RepeatMode::Playlist => pos.wrapping_add_signed(delta) % len,
         };
 
-        self.select(pos)
+        (pos < len).then_some(pos)
+    }
+
+    fn shuffled(&self, delta: isize, repeat_mode: RepeatMode, shuffle: Shuffle) -> Option<usize> {
+        let mut order = self.order.lock();
+        let len = self.items().len();
+        let pos = self.pos();
+
+        if order.mode() != shuffle || order.len() != len {
+            order.generate(
+                shuffle,
+                self.groups(shuffle, 0..len),
+                (pos < len).then_some(pos),
+            );
+        }
+
+        let rank = order.rank(pos)?;
+        let next = rank.checked_add_signed(delta).filter(|next| *next < len);
+
+        match (next, repeat_mode) {
+            (Some(next), _) => order.get(next),
+            (None, RepeatMode::Disabled) => None,
+            (None, _) if delta > 0 => {
+                order.cycle(self.groups(shuffle, 0..len), pos);
+                order.get(delta as usize % len)
+            }
+            (None, _) => order.get((rank as isize + delta).rem_euclid(len as isize) as usize),
+        }
+    }
+
+    fn groups(&self, shuffle: Shuffle, range: Range<usize>) -> Vec<Range<usize>> {
+        let items = self.items();
+        let mut groups = Vec::<Range<usize>>::new();
+
+        for i in range {
+            let key = |i: usize| {
+                let metadata = items[i].metadata();
+                (metadata.album(), metadata.album_artist())
+            };
+
+            match groups.last_mut() {
+                Some(group)
+                    if shuffle == Shuffle::Album
+                        && key(i).0.is_some()
+                        && key(i) == key(group.end - 1) =>
+                {
+                    group.end = i + 1;
+                }
+                _ => groups.push(i..i + 1),
+            }
+        }
+
+        groups
     }
 
     pub fn can_translate(&self, delta: isize) -> bool {
@@ -110,9 +566,145 @@ where
     }
 }
 
+impl<T> Factory<T>
+where
+    T: Write<Item = f32> + Seek + Tell + Release + Describe + Normalize,
+{
+    fn prepare(&self, remaining: Duration, spec: Spec) {
+        let (duration, curve) = {
+            let mut state = self.state();
+            (*state.crossfade(), state.crossfade_curve().get())
+        };
+
+        if duration <= 0.0 {
+            return;
+        }
+
+        // Mixing buffers are set up ahead of time, so starting a fade does not allocate.
+        let mut bufs = self.bufs.lock();
+
+        if !bufs.as_ref().is_some_and(|(a, _)| a.spec() == spec) {
+            bufs.replace((Seq::with_spec(spec), Seq::with_spec(spec)));
+        }
+
+        drop(bufs);
+
+        if remaining.as_secs_f32() > duration {
+            return;
+        }
+
+        let pos = self.pos();
+        let next = match self.target(1, TranslateBehavior::Modal) {
+            Some(next) if next != pos => next,
+            _ => return,
+        };
+        let ready = *self.preloaded.lock() == Some(next);
+        let mut items = self.items();
+
+        if items.get(pos).is_none() {
+            return;
+        }
+
+        let (a, b) = (items[pos].metadata(), items[next].metadata());
+
+        if a.album().is_some() && a.album() == b.album() && a.album_artist() == b.album_artist() {
+            return;
+        }
+
+        if !ready && !items[next].rewind() {
+            return;
+        }
+
+        drop(items);
+        self.preloaded.lock().take();
+
+        let frames = (remaining.as_secs_f64() * spec.rate() as f64) as usize;
+        let fade = Fade {
+            next,
+            frames: std::cmp::max(frames, 1),
+            elapsed: 0,
+            curve,
+        };
+
+        self.fade.lock().replace(fade);
+    }
+
+    fn crossfade<U>(&self, fade: &mut Fade, dst: &mut U) -> usize
+    where
+        U: BufMut,
+        U::Item: Sample + FromSample<f32>,
+    {
+        let spec = dst.spec();
+        let cap = spec.frames();
+        let want = std::cmp::min(cap - dst.len(), fade.frames - fade.elapsed);
+        let start = cap - want;
+        let (mode, preamp) = {
+            let mut state = self.state();
+            (state.replaygain().get(), *state.replaygain_preamp())
+        };
+        let mut bufs = self.bufs.lock();
+
+        if bufs.as_ref().is_some_and(|(a, _)| a.spec() != spec) {
+            bufs.take();
+        }
+
+        let (a, b) = bufs.get_or_insert_with(|| (Seq::with_spec(spec), Seq::with_spec(spec)));
+        let mut items = self.items();
+
+        for (pos, seq) in [(self.pos(), &mut *a), (fade.next, &mut *b)] {
+            seq.set_len(start);
+            seq.set_pos(start);
+
+            if let Some(item) = items.get_mut(pos) {
+                while seq.len() < cap && item.write(seq) > 0 {}
+            }
+        }
+
+        // The gain stage applies the outgoing entry's ReplayGain to the mix, so the incoming one
+        // is scaled to end up at its own.
+        let scale = match (items.get(self.pos()), items.get(fade.next)) {
+            (Some(prev), Some(next)) => {
+                let from = prev.replay_gain().level(mode, preamp);
+                let to = next.replay_gain().level(mode, preamp);
+
+                if from > 0.0 {
+                    to / from
+                } else {
+                    1.0
+                }
+            }
+            _ => 1.0,
+        };
+
+        drop(items);
+
+        let (la, lb) = (a.len(), b.len());
+
+        for i in 0..want {
+            let t = (fade.elapsed + i) as f32 / fade.frames as f32;
+            let (ga, gb) = fade.curve.gains(t);
+            let k = start + i;
+            let ga = if k < la { ga } else { 0.0 };
+            let gb = if k < lb { gb * scale } else { 0.0 };
+            let src = b.frame(k);
+
+            for (a, b) in a.frame_mut(k).iter_mut().zip(src.iter()) {
+                *a = *a * ga + *b * gb;
+            }
+        }
+
+        a.set_len(cap);
+        a.set_pos(start);
+        a.write(dst);
+        fade.elapsed += want;
+
+        want
+    }
+}
+
 impl<T> Write for Arc<Factory<T>>
 where
-    T: Write<Item = f32> + Seek,
+    T: Write<Item = f32> + Seek + Tell + Release + Describe + Normalize,
 {
     type Item = f32;
 
@@ -121,18 +713,88 @@ where
         U: BufMut,
         U::Item: Sample + FromSample<Self::Item>,
     {
-        let pos = self.pos();
-        let mut n = 0;
+        let mut fade = self.fade.lock();
+
+        if let Some(f) = fade.as_mut() {
+            let n = self.crossfade(f, buf);
 
-        if let Some(item) = self.items().get_mut(pos) {
-            n = item.write(buf);
+            if f.elapsed >= f.frames {
+                self.shift(f.next);
+                fade.take();
+            }
+
+            return n;
         }
 
-        if n == 0 {
-            self.translate(1, TranslateBehavior::Modal);
+        drop(fade);
+
+        let p = buf.len();
+        let len = self.items().len();
+
+        // Carry on with the upcoming entry in the same buffer so transitions stay gapless, and
+        // step over every entry that fails to open so an empty write only means the end.
+        for _ in 0..=len {
+            let pos = self.pos();
+            let (n, remaining) = match self.items().get_mut(pos) {
+                Some(item) => (item.write(buf), item.remaining()),
+                None => (0, None),
+            };
+
+            if n > 0 {
+                self.ended.store(false, Ordering::Relaxed);
+                self.preload();
+
+                if let Some(remaining) = remaining {
+                    self.prepare(remaining, buf.spec());
+                }
+
+                break;
+            }
+
+            if !self.advance(pos) {
+                if !self.ended.swap(true, Ordering::Relaxed) {
+                    self.emit(Event::EndOfQueue);
+                }
+
+                break;
+            }
         }
 
-        n
+        buf.len() - p
+    }
+
+    fn set_latency(&mut self, latency: Duration) {
+        *self.latency.lock() = latency;
+    }
+}
+
+impl<T> Level for Factory<T>
+where
+    T: Normalize,
+{
+    fn level(&self) -> f32 {
+        let mut state = self.state();
+
+        match *state.mute() {
+            true => 0.0,
+            false => state.volume().level(),
+        }
+    }
+
+    fn normalization(&self) -> (usize, f32) {
+        let mut state = self.state();
+        let mode = state.replaygain().get();
+        let preamp = *state.replaygain_preamp();
+        drop(state);
+
+        let items = self.items();
+        let track = self.track.load(Ordering::SeqCst);
+        let level = match items.get(self.pos()) {
+            Some(item) => item.replay_gain().level(mode, preamp),
+            None => 1.0,
+        };
+
+        (track, level)
     }
 }
 
@@ -142,10 +804,20 @@ impl<T> Default for Factory<T> {
     }
 }
 
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub struct FactoryState {
     repeat_mode: RepeatMode,
+    resample_quality: Quality,
+    volume: Volume,
+    mute: bool,
+    replaygain: ReplayGainMode,
+    replaygain_preamp: f32,
+    crossfade: f32,
+    crossfade_curve: Curve,
+    shuffle: Shuffle,
+    device: Option<String>,
 }
 
 impl FactoryState {
@@ -153,6 +825,42 @@ impl FactoryState {
         &mut self.repeat_mode
     }
 
+    pub fn resample_quality(&mut self) -> &mut Quality {
+        &mut self.resample_quality
+    }
+
+    pub fn volume(&mut self) -> &mut Volume {
+        &mut self.volume
+    }
+
+    pub fn mute(&mut self) -> &mut bool {
+        &mut self.mute
+    }
+
+    pub fn replaygain(&mut self) -> &mut ReplayGainMode {
+        &mut self.replaygain
+    }
+
+    pub fn replaygain_preamp(&mut self) -> &mut f32 {
+        &mut self.replaygain_preamp
+    }
+
+    pub fn crossfade(&mut self) -> &mut f32 {
+        &mut self.crossfade
+    }
+
+    pub fn crossfade_curve(&mut self) -> &mut Curve {
+        &mut self.crossfade_curve
+    }
+
+    pub fn shuffle(&mut self) -> &mut Shuffle {
+        &mut self.shuffle
+    }
+
+    pub fn device(&mut self) -> &mut Option<String> {
+        &mut self.device
+    }
+
     pub fn replace(&mut self, src: FactoryState) -> Self {
         std::mem::replace(self, src)
     }
@@ -162,6 +870,15 @@ impl Default for FactoryState {
     fn default() -> Self {
         Self {
             repeat_mode: RepeatMode::Disabled,
+            resample_quality: Quality::High,
+            volume: Volume::default(),
+            mute: false,
+            replaygain: ReplayGainMode::Off,
+            replaygain_preamp: 0.0,
+            crossfade: 0.0,
+            crossfade_curve: Curve::EqualPower,
+            shuffle: Shuffle::Off,
+            device: None,
         }
     }
 }
@@ -190,3 +907,190 @@ pub enum TranslateBehavior {
     Free,
     Modal,
 }
+
+fn release<T>(items: &mut [T], indices: &[Option<usize>], keep: usize)
+where
+    T: Release,
+{
+    for i in indices.iter().flatten() {
+        if let Some(item) = items.get_mut(*i).filter(|_| *i != keep) {
+            item.release();
+        }
+    }
+}
+
+struct Fade {
+    next: usize,
+    frames: usize,
+    elapsed: usize,
+    curve: Curve,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::Metadata;
+
+    struct Item {
+        id: usize,
+        metadata: Metadata,
+    }
+
+    impl Seek for Item {
+        fn seek(&mut self, _pos: SeekFrom) -> bool {
+            true
+        }
+    }
+
+    impl Tell for Item {
+        fn position(&self) -> Duration {
+            Duration::ZERO
+        }
+
+        fn duration(&self) -> Option<Duration> {
+            Some(Duration::from_secs(1))
+        }
+    }
+
+    impl Release for Item {}
+
+    impl Describe for Item {
+        fn metadata(&self) -> &Metadata {
+            &self.metadata
+        }
+    }
+
+    fn factory(len: usize, pos: usize) -> Factory<Item> {
+        let factory = Factory::new();
+        factory.extend((0..len).map(|id| Item {
+            id,
+            metadata: Metadata::default(),
+        }));
+        assert!(factory.select(pos));
+        factory
+    }
+
+    fn ids(factory: &Factory<Item>) -> Vec<usize> {
+        factory.inspect(|items| items.iter().map(|item| item.id).collect())
+    }
+
+    fn current(factory: &Factory<Item>) -> Option<usize> {
+        factory.inspect(|items| items.get(factory.pos()).map(|item| item.id))
+    }
+
+    fn remove(factory: &Factory<Item>, range: Range<usize>) -> bool {
+        factory.remove(std::slice::from_ref(&range))
+    }
+
+    fn shuffled(factory: &Factory<Item>) -> Vec<usize> {
+        let ids = ids(factory);
+        factory.order.lock().iter().map(|i| ids[i]).collect()
+    }
+
+    #[test]
+    fn insert_before_current() {
+        let factory = factory(3, 1);
+        factory.insert(
+            0,
+            (10..12).map(|id| Item {
+                id,
+                metadata: Metadata::default(),
+            }),
+        );
+
+        assert_eq!(ids(&factory), [10, 11, 0, 1, 2]);
+        assert_eq!(current(&factory), Some(1));
+    }
+
+    #[test]
+    fn remove_before_current() {
+        let factory = factory(5, 3);
+
+        assert!(!remove(&factory, 0..2));
+        assert_eq!(ids(&factory), [2, 3, 4]);
+        assert_eq!(current(&factory), Some(3));
+    }
+
+    #[test]
+    fn remove_current() {
+        let factory = factory(5, 1);
+
+        assert!(remove(&factory, 1..3));
+        assert_eq!(ids(&factory), [0, 3, 4]);
+        assert_eq!(current(&factory), Some(3));
+    }
+
+    #[test]
+    fn remove_ranges() {
+        let factory = factory(6, 4);
+
+        assert!(!factory.remove(&[0..1, 2..4]));
+        assert_eq!(ids(&factory), [1, 4, 5]);
+        assert_eq!(current(&factory), Some(4));
+    }
+
+    #[test]
+    fn remove_last() {
+        let factory = factory(3, 2);
+
+        assert!(remove(&factory, 2..3));
+        assert_eq!(factory.pos(), 2);
+        assert_eq!(current(&factory), None);
+    }
+
+    #[test]
+    fn remove_last_repeated() {
+        let factory = factory(3, 2);
+        factory.state().repeat_mode().set(RepeatMode::Playlist);
+
+        assert!(remove(&factory, 1..3));
+        assert_eq!(current(&factory), Some(0));
+    }
+
+    #[test]
+    fn remove_shuffled() {
+        let factory = factory(8, 0);
+        factory.state().shuffle().set(Shuffle::Track);
+        assert!(factory.translate(1, TranslateBehavior::Modal));
+
+        let before = shuffled(&factory);
+        let pos = before.iter().position(|id| Some(*id) == current(&factory));
+        let next = before[pos.unwrap() + 1];
+
+        assert!(remove(&factory, factory.pos()..factory.pos() + 1));
+        assert_eq!(current(&factory), Some(next));
+
+        let after = shuffled(&factory);
+        let expected = before.into_iter().filter(|id| after.contains(id));
+        assert!(after.iter().copied().eq(expected));
+        assert_eq!(after.len(), 7);
+    }
+
+    #[test]
+    fn reorder() {
+        let factory = factory(5, 1);
+        factory.reorder(0..2, 3);
+
+        assert_eq!(ids(&factory), [2, 3, 4, 0, 1]);
+        assert_eq!(current(&factory), Some(1));
+
+        factory.reorder(4..5, 0);
+
+        assert_eq!(ids(&factory), [1, 2, 3, 4, 0]);
+        assert_eq!(current(&factory), Some(1));
+    }
+
+    #[test]
+    fn reorder_shuffled() {
+        let factory = factory(6, 0);
+        factory.state().shuffle().set(Shuffle::Track);
+        assert!(factory.translate(1, TranslateBehavior::Modal));
+
+        let before = shuffled(&factory);
+        let id = current(&factory);
+        factory.reorder(1..3, 4);
+
+        assert_eq!(shuffled(&factory), before);
+        assert_eq!(current(&factory), id);
+    }
+}
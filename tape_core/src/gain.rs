@@ -0,0 +1,406 @@
+use crate::buf::{BufMut, Spec};
+use crate::io::Write;
+use cpal::{FromSample, Sample};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+const RAMP: f32 = 0.05;
+
+pub trait Level {
+    fn level(&self) -> f32;
+
+    /// The playing entry's ReplayGain level, along with a count that changes whenever playback
+    /// moves to another entry.
+    fn normalization(&self) -> (usize, f32) {
+        (0, 1.0)
+    }
+}
+
+pub trait Normalize {
+    fn replay_gain(&self) -> ReplayGain;
+}
+
+pub struct Gain<T> {
+    inner: Arc<T>,
+    ramp: Mutex<Option<Ramp>>,
+}
+
+impl<T> Gain<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            ramp: Mutex::new(None),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        self.inner.as_ref()
+    }
+}
+
+impl<T> Write for Arc<Gain<T>>
+where
+    T: Level,
+    Arc<T>: Write<Item = f32>,
+{
+    type Item = f32;
+
+    fn write<U>(&mut self, dst: &mut U) -> usize
+    where
+        U: BufMut,
+        U::Item: Sample + FromSample<Self::Item>,
+    {
+        let p = dst.len();
+        let n = self.inner.clone().write(dst);
+        let volume = self.inner.level();
+        let (track, normalization) = self.inner.normalization();
+        let mut ramp = self.ramp.lock();
+        let ramp = ramp.get_or_insert(Ramp {
+            track,
+            volume,
+            normalization,
+        });
+
+        // A new entry starts out at its own level, only changes during playback are ramped.
+        if ramp.track != track {
+            ramp.track = track;
+            ramp.normalization = normalization;
+        }
+
+        let settled = ramp.volume == volume && ramp.normalization == normalization;
+
+        if settled && volume * normalization == 1.0 {
+            return n;
+        }
+
+        let step = 1.0 / (RAMP * dst.spec().rate() as f32);
+
+        for mut frame in dst.frames_mut().skip(p).take(n) {
+            ramp.volume = approach(ramp.volume, volume, step);
+            ramp.normalization = approach(ramp.normalization, normalization, step);
+
+            let amp = <U::Item as Sample>::Float::from_sample_(ramp.volume * ramp.normalization);
+
+            for sample in frame.iter_mut() {
+                *sample = sample.mul_amp(amp);
+            }
+        }
+
+        n
+    }
+
+    fn format(&self) -> Option<Spec> {
+        self.inner.format()
+    }
+
+    fn set_latency(&mut self, latency: Duration) {
+        self.inner.clone().set_latency(latency);
+    }
+}
+
+struct Ramp {
+    track: usize,
+    volume: f32,
+    normalization: f32,
+}
+
+fn approach(current: f32, target: f32, step: f32) -> f32 {
+    if current < target {
+        f32::min(current + step, target)
+    } else {
+        f32::max(current - step, target)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Volume(f32);
+
+impl Volume {
+    pub const MAX: f32 = 1.0;
+
+    pub fn new(level: f32) -> Self {
+        Self(level.clamp(0.0, Self::MAX))
+    }
+
+    pub fn get(&self) -> Self {
+        *self
+    }
+
+    pub fn set(&mut self, volume: Self) {
+        *self = volume;
+    }
+
+    pub fn level(&self) -> f32 {
+        self.0
+    }
+
+    /// Returns false when the requested level was out of range and got clamped.
+    pub fn adjust(&mut self, adjustment: Adjustment) -> bool {
+        let level = match adjustment {
+            Adjustment::Absolute(level) => level,
+            Adjustment::Relative(delta) => self.0 + delta,
+            Adjustment::Scale(factor) => self.0 * factor,
+        };
+
+        *self = Self::new(level);
+        self.0 == level
+    }
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self(Self::MAX)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ReplayGain {
+    pub track: Option<Normalization>,
+    pub album: Option<Normalization>,
+}
+
+impl ReplayGain {
+    pub fn level(&self, mode: ReplayGainMode, preamp: f32) -> f32 {
+        let normalization = match mode {
+            ReplayGainMode::Off => None,
+            ReplayGainMode::Track => self.track.or(self.album),
+            ReplayGainMode::Album => self.album.or(self.track),
+        };
+
+        normalization.map_or(1.0, |normalization| normalization.level(preamp))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Normalization {
+    pub gain: f32,
+    pub peak: Option<f32>,
+}
+
+impl Normalization {
+    pub fn level(&self, preamp: f32) -> f32 {
+        let level = db_to_amp(self.gain + preamp);
+
+        match self.peak {
+            Some(peak) if peak > 0.0 => f32::min(level, 1.0 / peak),
+            // Without a peak there is no telling how much headroom is left.
+            _ => f32::min(level, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum ReplayGainMode {
+    Off,
+    Track,
+    Album,
+}
+
+impl ReplayGainMode {
+    pub fn get(&self) -> Self {
+        *self
+    }
+
+    pub fn set(&mut self, mode: Self) {
+        *self = mode;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Adjustment {
+    Absolute(f32),
+    Relative(f32),
+    Scale(f32),
+}
+
+impl FromStr for Adjustment {
+    type Err = GainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let relative = s.starts_with(['+', '-']);
+        let (value, decibels) = match s.strip_suffix("dB").or_else(|| s.strip_suffix("db")) {
+            Some(value) => (value, true),
+            None => (s.strip_suffix('%').unwrap_or(s), false),
+        };
+        let value = value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| GainError::Invalid(s.into()))?;
+
+        let adjustment = match (relative, decibels) {
+            (true, true) => Self::Scale(db_to_amp(value)),
+            (false, true) => Self::Absolute(db_to_amp(value)),
+            (true, false) => Self::Relative(value / 100.0),
+            (false, false) => Self::Absolute(value / 100.0),
+        };
+
+        Ok(adjustment)
+    }
+}
+
+pub fn db_to_amp(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+#[derive(Error, Debug)]
+pub enum GainError {
+    #[error("{0}: invalid volume")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buf::{Buf, Layout, Seq};
+
+    struct Levels {
+        volume: Mutex<f32>,
+        normalization: Mutex<(usize, f32)>,
+    }
+
+    impl Level for Levels {
+        fn level(&self) -> f32 {
+            *self.volume.lock()
+        }
+
+        fn normalization(&self) -> (usize, f32) {
+            *self.normalization.lock()
+        }
+    }
+
+    impl Write for Arc<Levels> {
+        type Item = f32;
+
+        fn write<U>(&mut self, dst: &mut U) -> usize
+        where
+            U: BufMut,
+            U::Item: Sample + FromSample<Self::Item>,
+        {
+            let p = dst.len();
+            let n = dst.spec().frames() - p;
+
+            for mut frame in dst.frames_mut().skip(p) {
+                for sample in frame.iter_mut() {
+                    *sample = U::Item::from_sample(1.0);
+                }
+            }
+
+            dst.set_len(p + n);
+
+            n
+        }
+    }
+
+    fn gain(volume: f32, normalization: f32) -> Arc<Gain<Levels>> {
+        let levels = Levels {
+            volume: Mutex::new(volume),
+            normalization: Mutex::new((0, normalization)),
+        };
+
+        Arc::new(Gain::new(levels))
+    }
+
+    fn render(gain: &mut Arc<Gain<Levels>>) -> Vec<f32> {
+        let mut dst = Seq::with_spec(Spec::new(4800, Layout::MONO, 48000));
+        gain.write(&mut dst);
+        dst.frames().map(|frame| frame.into_vec()[0]).collect()
+    }
+
+    fn adjust(level: f32, s: &str) -> (f32, bool) {
+        let mut volume = Volume::new(level);
+        let exact = volume.adjust(s.parse().unwrap());
+        (volume.level(), exact)
+    }
+
+    #[test]
+    fn parse_adjustment() {
+        assert!(matches!("50%".parse(), Ok(Adjustment::Absolute(level)) if level == 0.5));
+        assert!(matches!("25".parse(), Ok(Adjustment::Absolute(level)) if level == 0.25));
+        assert!(matches!(" +10% ".parse(), Ok(Adjustment::Relative(delta)) if delta == 0.1));
+        assert!(matches!("-10".parse(), Ok(Adjustment::Relative(delta)) if delta == -0.1));
+        assert!(matches!("0dB".parse(), Ok(Adjustment::Absolute(level)) if level == 1.0));
+        assert!(matches!("-6 db".parse(), Ok(Adjustment::Scale(factor)) if factor < 0.51));
+        assert!(matches!("+0dB".parse(), Ok(Adjustment::Scale(factor)) if factor == 1.0));
+    }
+
+    #[test]
+    fn parse_invalid_adjustment() {
+        for s in ["", "%", "dB", "loud", "inf", "NaN%", "10%%", "1dB%"] {
+            assert!(s.parse::<Adjustment>().is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn adjust_volume() {
+        assert_eq!(adjust(1.0, "50%"), (0.5, true));
+        assert_eq!(adjust(0.5, "+20%"), (0.7, true));
+        assert_eq!(adjust(0.5, "+80%"), (1.0, false));
+        assert_eq!(adjust(0.5, "-80%"), (0.0, false));
+        assert_eq!(adjust(0.5, "150%"), (1.0, false));
+        assert_eq!(adjust(1.0, "+0dB"), (1.0, true));
+    }
+
+    #[test]
+    fn replay_gain_clamps_to_peak() {
+        let track = Normalization {
+            gain: 6.0,
+            peak: Some(0.8),
+        };
+        let gain = ReplayGain {
+            track: Some(track),
+            album: None,
+        };
+
+        assert_eq!(gain.level(ReplayGainMode::Off, 0.0), 1.0);
+        assert_eq!(gain.level(ReplayGainMode::Album, 0.0), 1.0 / 0.8);
+        assert!(gain.level(ReplayGainMode::Track, -12.0) < 0.6);
+        assert_eq!(
+            Normalization {
+                gain: 6.0,
+                peak: None
+            }
+            .level(0.0),
+            1.0
+        );
+    }
+
+    #[test]
+    fn new_entry_starts_at_its_level() {
+        let mut gain = gain(1.0, 1.0);
+        render(&mut gain);
+
+        *gain.inner().normalization.lock() = (1, 0.5);
+
+        assert!(render(&mut gain).iter().all(|sample| *sample == 0.5));
+    }
+
+    #[test]
+    fn changes_during_playback_ramp() {
+        let mut gain = gain(1.0, 1.0);
+        render(&mut gain);
+
+        *gain.inner().volume.lock() = 0.5;
+        let out = render(&mut gain);
+
+        assert!(out[0] < 1.0 && out[0] > 0.5);
+        assert_eq!(out[out.len() - 1], 0.5);
+
+        // Switching ReplayGain modes changes the level of the same entry.
+        *gain.inner().normalization.lock() = (0, 0.5);
+        let out = render(&mut gain);
+
+        assert!(out[0] < 0.5 && out[0] > 0.25);
+        assert_eq!(out[out.len() - 1], 0.25);
+    }
+}
@@ -1,14 +1,114 @@
-use crate::buf::{Buf, BufMut};
+use crate::buf::{Buf, BufMut, Spec};
+use crate::mix::Matrix;
 use cpal::{FromSample, Sample};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
 
 pub trait Seek {
-    fn seek(&mut self, t: u64) -> bool;
+    fn seek(&mut self, pos: SeekFrom) -> bool;
 
     fn rewind(&mut self) -> bool {
-        self.seek(0)
+        self.seek(SeekFrom::Start(Duration::ZERO))
     }
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SeekFrom {
+    Start(Duration),
+    Forward(Duration),
+    Backward(Duration),
+    Fraction(f64),
+}
+
+impl SeekFrom {
+    pub fn resolve(&self, position: Duration, duration: Option<Duration>) -> Option<Duration> {
+        let t = match *self {
+            Self::Start(t) => t,
+            Self::Forward(delta) => position + delta,
+            Self::Backward(delta) => position.saturating_sub(delta),
+            Self::Fraction(fraction) => duration?.mul_f64(fraction.clamp(0.0, 1.0)),
+        };
+
+        match duration {
+            Some(duration) => Some(std::cmp::min(t, duration)),
+            None => Some(t),
+        }
+    }
+}
+
+impl FromStr for SeekFrom {
+    type Err = SeekError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || SeekError::Invalid(s.into());
+
+        if let Some(value) = s.strip_suffix('%') {
+            let value = value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| (0.0..=100.0).contains(value))
+                .ok_or_else(invalid)?;
+
+            return Ok(Self::Fraction(value / 100.0));
+        }
+
+        let (sign, value) = match s.strip_prefix('+') {
+            Some(value) => (Some(true), value),
+            None => match s.strip_prefix('-') {
+                Some(value) => (Some(false), value),
+                None => (None, s),
+            },
+        };
+        let mut secs = 0.0;
+
+        for (i, part) in value.rsplit(':').enumerate() {
+            let part = part
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|part| part.is_finite() && *part >= 0.0)
+                .ok_or_else(invalid)?;
+
+            secs += match i {
+                0 => part,
+                1 => part * 60.0,
+                2 => part * 3600.0,
+                _ => return Err(invalid()),
+            };
+        }
+
+        let t = Duration::try_from_secs_f64(secs).map_err(|_| invalid())?;
+        let pos = match sign {
+            Some(true) => Self::Forward(t),
+            Some(false) => Self::Backward(t),
+            None => Self::Start(t),
+        };
+
+        Ok(pos)
+    }
+}
+
+pub trait Tell {
+    fn position(&self) -> Duration;
+
+    fn duration(&self) -> Option<Duration>;
+
+    fn remaining(&self) -> Option<Duration> {
+        Some(self.duration()?.saturating_sub(self.position()))
+    }
+}
+
+pub trait Release {
+    fn release(&mut self) {}
+}
+
 pub trait Write {
     type Item: Sample;
 
@@ -17,6 +117,12 @@ pub trait Write {
         U: BufMut,
         U::Item: Sample + FromSample<Self::Item>;
 
+    fn format(&self) -> Option<Spec> {
+        None
+    }
+
+    fn set_latency(&mut self, _latency: Duration) {}
+
     fn write_all<U>(&mut self, dst: &mut U)
     where
         U: BufMut,
@@ -28,6 +134,11 @@ pub trait Write {
     }
 }
 
+thread_local! {
+    // Plain buffers have nowhere to keep a matrix, so the last one is kept per thread.
+    static MATRIX: RefCell<Option<Matrix>> = const { RefCell::new(None) };
+}
+
 impl<T> Write for T
 where
     T: Buf,
@@ -42,13 +153,21 @@ where
     {
         let p1 = self.pos();
         let p2 = dst.len();
+        let layout = Buf::spec(self).layout();
         let mut n = 0;
 
-        for (src, mut dst) in self.frames().skip(p1).zip(dst.frames_mut().skip(p2)) {
-            for (src, dst) in src.iter().zip(dst.iter_mut()) {
-                *dst = U::Item::from_sample(*src);
-            }
-            n += 1;
+        if layout == dst.spec().layout() {
+            n = crate::buf::copy(self, dst);
+        } else {
+            MATRIX.with_borrow_mut(|slot| {
+                let matrix = Matrix::cached(slot, layout, dst.spec().layout());
+                let frames = self.frames().take(self.len()).skip(p1);
+
+                for (src, dst) in frames.zip(dst.frames_mut().skip(p2)) {
+                    matrix.apply(src, dst);
+                    n += 1;
+                }
+            });
         }
 
         self.set_pos(p1 + n);
@@ -56,4 +175,71 @@ where
 
         n
     }
+
+    fn format(&self) -> Option<Spec> {
+        Some(Buf::spec(self))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SeekError {
+    #[error("{0}: invalid position")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Option<SeekFrom> {
+        s.parse().ok()
+    }
+
+    fn secs(pos: Option<SeekFrom>) -> Option<(char, f64)> {
+        match pos? {
+            SeekFrom::Start(t) => Some(('=', t.as_secs_f64())),
+            SeekFrom::Forward(t) => Some(('+', t.as_secs_f64())),
+            SeekFrom::Backward(t) => Some(('-', t.as_secs_f64())),
+            SeekFrom::Fraction(fraction) => Some(('%', fraction)),
+        }
+    }
+
+    #[test]
+    fn parse_seek() {
+        assert_eq!(secs(parse("90")), Some(('=', 90.0)));
+        assert_eq!(secs(parse("1:30")), Some(('=', 90.0)));
+        assert_eq!(secs(parse("1:00:01.5")), Some(('=', 3601.5)));
+        assert_eq!(secs(parse("+5")), Some(('+', 5.0)));
+        assert_eq!(secs(parse("-0:10")), Some(('-', 10.0)));
+        assert_eq!(secs(parse(" 50% ")), Some(('%', 0.5)));
+        assert_eq!(secs(parse("100%")), Some(('%', 1.0)));
+    }
+
+    #[test]
+    fn parse_invalid_seek() {
+        for s in [
+            "", "abc", "1:2:3:4", "-5%", "101%", "+-5", "1::2", "inf", "-1:-1",
+        ] {
+            assert!(parse(s).is_none(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn resolve_seek() {
+        let position = Duration::from_secs(30);
+        let duration = Some(Duration::from_secs(60));
+        let resolve = |pos: SeekFrom| pos.resolve(position, duration).map(|t| t.as_secs());
+
+        assert_eq!(resolve(SeekFrom::Start(Duration::from_secs(10))), Some(10));
+        assert_eq!(
+            resolve(SeekFrom::Forward(Duration::from_secs(45))),
+            Some(60)
+        );
+        assert_eq!(
+            resolve(SeekFrom::Backward(Duration::from_secs(45))),
+            Some(0)
+        );
+        assert_eq!(resolve(SeekFrom::Fraction(0.25)), Some(15));
+        assert_eq!(SeekFrom::Fraction(0.5).resolve(position, None), None);
+    }
 }
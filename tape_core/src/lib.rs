@@ -1,11 +1,24 @@
-#![feature(ptr_as_ref_unchecked)]
-
 pub mod buf;
+pub mod device;
 pub mod engine;
+pub mod entry;
+pub mod event;
+pub mod export;
 pub mod factory;
+pub mod gain;
 pub mod io;
+pub mod meta;
+pub mod mix;
+pub mod output;
+pub mod pump;
+pub mod resample;
+pub mod ring;
+pub mod shuffle;
 pub mod sound;
 
 pub use engine::Engine;
+pub use entry::Entry;
 pub use factory::Factory;
+pub use gain::Gain;
+pub use resample::Resampler;
 pub use sound::Sound;
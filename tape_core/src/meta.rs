@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use symphonia::meta::{StandardTagKey, StandardVisualKey, Tag, Value, Visual};
+
+pub trait Describe {
+    fn metadata(&self) -> &Metadata;
+}
+
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub struct Metadata {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    album_artist: Option<String>,
+    track: Option<u32>,
+    track_total: Option<u32>,
+    disc: Option<u32>,
+    disc_total: Option<u32>,
+    date: Option<String>,
+    genre: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cover: Option<Cover>,
+}
+
+impl Metadata {
+    pub(crate) fn read(tags: &[Tag], visuals: &[Visual]) -> Self {
+        let mut metadata = Self::default();
+
+        for tag in tags {
+            let key = match tag.std_key {
+                Some(key) => key,
+                None => continue,
+            };
+
+            match key {
+                StandardTagKey::TrackTitle => metadata.title = read_string(&tag.value),
+                StandardTagKey::Artist => metadata.artist = read_string(&tag.value),
+                StandardTagKey::Album => metadata.album = read_string(&tag.value),
+                StandardTagKey::AlbumArtist => metadata.album_artist = read_string(&tag.value),
+                StandardTagKey::Date | StandardTagKey::ReleaseDate => {
+                    metadata.date = read_string(&tag.value)
+                }
+                StandardTagKey::Genre => metadata.genre = read_string(&tag.value),
+                StandardTagKey::TrackNumber => {
+                    let (n, total) = read_position(&tag.value);
+                    metadata.track = n.or(metadata.track);
+                    metadata.track_total = total.or(metadata.track_total);
+                }
+                StandardTagKey::TrackTotal => metadata.track_total = read_position(&tag.value).0,
+                StandardTagKey::DiscNumber => {
+                    let (n, total) = read_position(&tag.value);
+                    metadata.disc = n.or(metadata.disc);
+                    metadata.disc_total = total.or(metadata.disc_total);
+                }
+                StandardTagKey::DiscTotal => metadata.disc_total = read_position(&tag.value).0,
+                _ => (),
+            }
+        }
+
+        let cover = visuals
+            .iter()
+            .find(|visual| matches!(visual.usage, Some(StandardVisualKey::FrontCover)))
+            .or_else(|| visuals.first());
+
+        metadata.cover = cover.map(|visual| Cover {
+            media_type: visual.media_type.clone(),
+            data: visual.data.clone(),
+        });
+
+        metadata
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn artist(&self) -> Option<&str> {
+        self.artist.as_deref()
+    }
+
+    pub fn album(&self) -> Option<&str> {
+        self.album.as_deref()
+    }
+
+    pub fn album_artist(&self) -> Option<&str> {
+        self.album_artist.as_deref()
+    }
+
+    pub fn track(&self) -> Option<u32> {
+        self.track
+    }
+
+    pub fn track_total(&self) -> Option<u32> {
+        self.track_total
+    }
+
+    pub fn disc(&self) -> Option<u32> {
+        self.disc
+    }
+
+    pub fn disc_total(&self) -> Option<u32> {
+        self.disc_total
+    }
+
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    pub fn genre(&self) -> Option<&str> {
+        self.genre.as_deref()
+    }
+
+    pub fn cover(&self) -> Option<&Cover> {
+        self.cover.as_ref()
+    }
+
+    pub fn without_cover(&self) -> Self {
+        Self {
+            cover: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Cover {
+    media_type: String,
+    data: Box<[u8]>,
+}
+
+impl Cover {
+    pub fn media_type(&self) -> &str {
+        &self.media_type
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+fn read_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Binary(_) | Value::Flag => None,
+        value => Some(value.to_string().trim().to_owned()).filter(|s| !s.is_empty()),
+    }
+}
+
+fn read_position(value: &Value) -> (Option<u32>, Option<u32>) {
+    match value {
+        Value::UnsignedInt(n) => (u32::try_from(*n).ok(), None),
+        Value::SignedInt(n) => (u32::try_from(*n).ok(), None),
+        Value::String(s) => {
+            let (n, total) = match s.split_once('/') {
+                Some((n, total)) => (n, Some(total)),
+                None => (s.as_str(), None),
+            };
+
+            (
+                n.trim().parse().ok(),
+                total.and_then(|total| total.trim().parse().ok()),
+            )
+        }
+        _ => (None, None),
+    }
+}
@@ -0,0 +1,331 @@
+use crate::buf::iter::{Frame, FrameMut};
+use crate::buf::Layout;
+use cpal::{FromSample, Sample};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+
+const MAX_CHANNELS: usize = u32::BITS as usize;
+const MAX_DEPTH: usize = 2;
+
+pub struct Matrix {
+    src: Layout,
+    dst: Layout,
+    coeffs: Vec<f32>,
+}
+
+impl Matrix {
+    pub fn new(src: Layout, dst: Layout) -> Self {
+        if src == dst || src.count() == 1 && dst.count() == 1 {
+            return Self {
+                src,
+                dst,
+                coeffs: Vec::new(),
+            };
+        }
+
+        let m = src.count();
+        let n = dst.count();
+        let mut coeffs = vec![0.0; m * n];
+        let mut routes = Vec::new();
+
+        if m == 1 {
+            let front = Layout::FRONT_LEFT
+                | Layout::FRONT_RIGHT
+                | Layout::FRONT_CENTER
+                | Layout::FRONT_LEFT_CENTER
+                | Layout::FRONT_RIGHT_CENTER;
+            let mut targets = dst.iter().filter(|p| front.contains(*p)).peekable();
+
+            if targets.peek().is_none() {
+                coeffs[0] = 1.0;
+            }
+
+            for position in targets {
+                if let Some(i) = dst.index(position) {
+                    coeffs[i] = 1.0;
+                }
+            }
+
+            return Self { src, dst, coeffs };
+        }
+
+        for (j, position) in src.iter().enumerate() {
+            routes.clear();
+            route(dst, position, 1.0, MAX_DEPTH, &mut routes);
+
+            for (target, gain) in &routes {
+                if let Some(i) = dst.index(*target) {
+                    coeffs[i * m + j] += gain;
+                }
+            }
+        }
+
+        let peak = coeffs
+            .chunks(m)
+            .map(|row| row.iter().sum::<f32>())
+            .fold(0.0, f32::max);
+
+        if peak > 1.0 {
+            for c in &mut coeffs {
+                *c /= peak;
+            }
+        }
+
+        Self { src, dst, coeffs }
+    }
+
+    pub fn cached(slot: &mut Option<Self>, src: Layout, dst: Layout) -> &Self {
+        if slot
+            .as_ref()
+            .is_some_and(|matrix| matrix.src != src || matrix.dst != dst)
+        {
+            slot.take();
+        }
+
+        slot.get_or_insert_with(|| Self::new(src, dst))
+    }
+
+    pub fn src(&self) -> Layout {
+        self.src
+    }
+
+    pub fn dst(&self) -> Layout {
+        self.dst
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.coeffs.is_empty()
+    }
+
+    pub fn apply<T, U>(&self, src: Frame<'_, T>, dst: FrameMut<'_, U>)
+    where
+        T: Sample,
+        U: Sample + FromSample<T>,
+    {
+        let mut dst = dst;
+
+        if self.is_identity() {
+            for (src, dst) in src.iter().zip(dst.iter_mut()) {
+                *dst = U::from_sample(*src);
+            }
+
+            return;
+        }
+
+        let mut buf = [0.0; MAX_CHANNELS];
+        let n = std::cmp::min(self.src.count(), MAX_CHANNELS);
+
+        for (buf, src) in buf.iter_mut().zip(src.iter()) {
+            *buf = to_f32(*src);
+        }
+
+        self.mix::<T, U>(&buf[..n], dst);
+    }
+
+    pub fn mix<T, U>(&self, src: &[f32], dst: FrameMut<'_, U>)
+    where
+        T: Sample,
+        U: Sample + FromSample<T>,
+    {
+        let mut dst = dst;
+
+        if self.is_identity() {
+            for (src, dst) in src.iter().zip(dst.iter_mut()) {
+                *dst = U::from_sample(from_f32::<T>(*src));
+            }
+
+            return;
+        }
+
+        let m = self.src.count();
+
+        for (row, dst) in self.coeffs.chunks(m).zip(dst.iter_mut()) {
+            let acc = row.iter().zip(src).map(|(c, s)| c * s).sum::<f32>();
+            *dst = U::from_sample(from_f32::<T>(acc));
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Curve {
+    Linear,
+    EqualPower,
+}
+
+impl Curve {
+    pub fn get(&self) -> Self {
+        *self
+    }
+
+    pub fn set(&mut self, curve: Self) {
+        *self = curve;
+    }
+
+    pub fn gains(&self, t: f32) -> (f32, f32) {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Self::Linear => (1.0 - t, t),
+            Self::EqualPower => ((t * FRAC_PI_2).cos(), (t * FRAC_PI_2).sin()),
+        }
+    }
+}
+
+fn route(dst: Layout, position: Layout, gain: f32, depth: usize, routes: &mut Vec<(Layout, f32)>) {
+    if dst.contains(position) {
+        routes.push((position, gain));
+        return;
+    }
+
+    if depth == 0 {
+        return;
+    }
+
+    let depth = depth - 1;
+    let attenuated = gain * FRAC_1_SQRT_2;
+
+    match position {
+        Layout::FRONT_CENTER => {
+            route(dst, Layout::FRONT_LEFT, attenuated, depth, routes);
+            route(dst, Layout::FRONT_RIGHT, attenuated, depth, routes);
+        }
+        Layout::FRONT_LEFT => route(dst, Layout::FRONT_CENTER, attenuated, depth, routes),
+        Layout::FRONT_RIGHT => route(dst, Layout::FRONT_CENTER, attenuated, depth, routes),
+        Layout::FRONT_LEFT_CENTER => route(dst, Layout::FRONT_LEFT, gain, depth, routes),
+        Layout::FRONT_RIGHT_CENTER => route(dst, Layout::FRONT_RIGHT, gain, depth, routes),
+        Layout::BACK_LEFT if dst.contains(Layout::SIDE_LEFT) => {
+            route(dst, Layout::SIDE_LEFT, gain, depth, routes)
+        }
+        Layout::BACK_RIGHT if dst.contains(Layout::SIDE_RIGHT) => {
+            route(dst, Layout::SIDE_RIGHT, gain, depth, routes)
+        }
+        Layout::SIDE_LEFT if dst.contains(Layout::BACK_LEFT) => {
+            route(dst, Layout::BACK_LEFT, gain, depth, routes)
+        }
+        Layout::SIDE_RIGHT if dst.contains(Layout::BACK_RIGHT) => {
+            route(dst, Layout::BACK_RIGHT, gain, depth, routes)
+        }
+        Layout::BACK_LEFT | Layout::SIDE_LEFT => {
+            route(dst, Layout::FRONT_LEFT, attenuated, depth, routes)
+        }
+        Layout::BACK_RIGHT | Layout::SIDE_RIGHT => {
+            route(dst, Layout::FRONT_RIGHT, attenuated, depth, routes)
+        }
+        Layout::BACK_CENTER => {
+            let (left, right) = if dst.contains(Layout::BACK_LEFT | Layout::BACK_RIGHT) {
+                (Layout::BACK_LEFT, Layout::BACK_RIGHT)
+            } else if dst.contains(Layout::SIDE_LEFT | Layout::SIDE_RIGHT) {
+                (Layout::SIDE_LEFT, Layout::SIDE_RIGHT)
+            } else {
+                (Layout::FRONT_LEFT, Layout::FRONT_RIGHT)
+            };
+
+            route(dst, left, attenuated, depth, routes);
+            route(dst, right, attenuated, depth, routes);
+        }
+        _ => (),
+    }
+}
+
+fn to_f32<T>(sample: T) -> f32
+where
+    T: Sample,
+{
+    sample.to_float_sample().to_sample()
+}
+
+fn from_f32<T>(sample: f32) -> T
+where
+    T: Sample,
+{
+    <T::Float as FromSample<f32>>::from_sample_(sample).to_sample()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(src: Layout, dst: Layout, frame: &[f32]) -> Vec<f32> {
+        let mut out = vec![0.0; dst.count()];
+        Matrix::new(src, dst).mix::<f32, f32>(frame, FrameMut::from_slice(&mut out));
+        out
+    }
+
+    fn close(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6)
+    }
+
+    #[test]
+    fn identity() {
+        assert!(Matrix::new(Layout::STEREO, Layout::STEREO).is_identity());
+        assert!(Matrix::new(Layout::MONO, Layout::FRONT_LEFT).is_identity());
+        assert_eq!(
+            mix(Layout::STEREO, Layout::STEREO, &[0.25, -0.5]),
+            [0.25, -0.5]
+        );
+    }
+
+    #[test]
+    fn mono_to_front() {
+        assert_eq!(mix(Layout::MONO, Layout::STEREO, &[0.5]), [0.5, 0.5]);
+        assert_eq!(
+            mix(Layout::MONO, Layout::SURROUND_5_1, &[0.5]),
+            [0.5, 0.5, 0.5, 0.0, 0.0, 0.0]
+        );
+        // A mono source with nothing in front falls back to the first channel.
+        let rear = Layout::BACK_LEFT | Layout::BACK_RIGHT;
+        assert_eq!(mix(Layout::MONO, rear, &[0.5]), [0.5, 0.0]);
+    }
+
+    #[test]
+    fn stereo_to_mono() {
+        let out = mix(Layout::STEREO, Layout::MONO, &[1.0, 1.0]);
+        assert!(close(&out, &[1.0]));
+
+        let out = mix(Layout::STEREO, Layout::MONO, &[1.0, 0.0]);
+        assert!(close(&out, &[0.5]));
+    }
+
+    #[test]
+    fn surround_to_stereo() {
+        // Centre and surrounds fold into the fronts, scaled so that no row exceeds unity.
+        let out = mix(Layout::SURROUND_5_1, Layout::STEREO, &[1.0; 6]);
+        assert!(close(&out, &[1.0, 1.0]));
+
+        let out = mix(
+            Layout::SURROUND_5_1,
+            Layout::STEREO,
+            &[0.0, 0.0, 1.0, 1.0, 0.0, 0.0],
+        );
+        let centre = FRAC_1_SQRT_2 / (1.0 + 2.0 * FRAC_1_SQRT_2);
+        assert!(close(&out, &[centre, centre]));
+    }
+
+    #[test]
+    fn side_and_back_swap() {
+        let src = Layout::STEREO | Layout::SIDE_LEFT | Layout::SIDE_RIGHT;
+        let out = mix(src, Layout::QUAD, &[0.1, 0.2, 0.3, 0.4]);
+        assert!(close(&out, &[0.1, 0.2, 0.3, 0.4]));
+    }
+
+    #[test]
+    fn cached() {
+        let mut slot = None;
+        let matrix = Matrix::cached(&mut slot, Layout::STEREO, Layout::MONO);
+        assert_eq!((matrix.src(), matrix.dst()), (Layout::STEREO, Layout::MONO));
+
+        let matrix = Matrix::cached(&mut slot, Layout::MONO, Layout::STEREO);
+        assert_eq!((matrix.src(), matrix.dst()), (Layout::MONO, Layout::STEREO));
+    }
+
+    #[test]
+    fn curves() {
+        assert_eq!(Curve::Linear.gains(0.25), (0.75, 0.25));
+        assert_eq!(Curve::Linear.gains(2.0), (0.0, 1.0));
+
+        let (a, b) = Curve::EqualPower.gains(0.5);
+        assert!((a * a + b * b - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,24 @@
+use crate::engine::EngineError;
+use std::sync::mpsc::Sender;
+
+pub mod cpal;
+pub mod null;
+
+pub use self::cpal::Cpal;
+pub use null::Null;
+
+pub trait Output<U> {
+    fn name(&self) -> Option<String>;
+
+    fn start(&mut self, provider: U, errors: Sender<EngineError>) -> Result<(), EngineError>;
+
+    fn stop(&mut self);
+
+    fn play(&mut self) -> Result<(), EngineError>;
+
+    fn pause(&mut self) -> Result<(), EngineError>;
+
+    fn reopen(&mut self, _fallback: bool) -> Result<(), EngineError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use super::Output;
+use crate::buf::{Layout, Spec};
+use crate::device;
+use crate::engine::EngineError;
+use crate::io::Write;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{
+    Device, FromSample, OutputCallbackInfo, SampleFormat, SizedSample, Stream, StreamConfig,
+};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tracing::debug;
+
+pub struct Cpal {
+    device: Device,
+    name: Option<String>,
+    stream: Option<Stream>,
+}
+
+impl Cpal {
+    pub fn open(name: Option<&str>) -> Result<Self, EngineError> {
+        let output = Self {
+            device: find_device(name)?,
+            name: name.map(Into::into),
+            stream: None,
+        };
+
+        Ok(output)
+    }
+
+    fn _start<T, U>(
+        &mut self,
+        config: &StreamConfig,
+        provider: U,
+        errors: Sender<EngineError>,
+    ) -> Result<(), EngineError>
+    where
+        T: SizedSample + FromSample<f32>,
+        U: 'static + Write<Item = f32> + Send,
+    {
+        let mut provider = provider;
+        let channels = config.channels as usize;
+        let layout = Layout::with_count(channels);
+        let rate = config.sample_rate.0;
+        let stream = self
+            .device
+            .build_output_stream(
+                config,
+                move |buf: &mut [T], info: &OutputCallbackInfo| {
+                    let frames = buf.len() / channels;
+                    let spec = Spec::new(frames, layout, rate);
+                    let mut dst = crate::buf::proxy::int_mut(buf, spec);
+                    provider.write_all(&mut dst);
+
+                    let timestamp = info.timestamp();
+                    let delay = timestamp
+                        .playback
+                        .duration_since(&timestamp.callback)
+                        .unwrap_or_default();
+                    let period = Duration::from_secs_f64(frames as f64 / rate as f64);
+                    provider.set_latency(delay + period);
+                },
+                move |e| {
+                    let _ = errors.send(e.into());
+                },
+                None,
+            )
+            .map_err(EngineError::ConnectionFailed)?;
+
+        debug!(
+            "Device configuration: channels: {}, sample rate: {}, sample format: {}",
+            config.channels,
+            config.sample_rate.0,
+            std::any::type_name::<T>()
+        );
+
+        self.stream.replace(stream);
+
+        Ok(())
+    }
+}
+
+impl<U> Output<U> for Cpal
+where
+    U: 'static + Write<Item = f32> + Send,
+{
+    fn name(&self) -> Option<String> {
+        self.device.name().ok()
+    }
+
+    fn start(&mut self, provider: U, errors: Sender<EngineError>) -> Result<(), EngineError> {
+        let config = self
+            .device
+            .default_output_config()
+            .map_err(|_| EngineError::Unsupported)?;
+
+        match config.sample_format() {
+            SampleFormat::U8 => self._start::<u8, U>(&config.into(), provider, errors),
+            SampleFormat::U16 => self._start::<u16, U>(&config.into(), provider, errors),
+            SampleFormat::U32 => self._start::<u32, U>(&config.into(), provider, errors),
+            SampleFormat::U64 => self._start::<u64, U>(&config.into(), provider, errors),
+            SampleFormat::I8 => self._start::<i8, U>(&config.into(), provider, errors),
+            SampleFormat::I16 => self._start::<i16, U>(&config.into(), provider, errors),
+            SampleFormat::I32 => self._start::<i32, U>(&config.into(), provider, errors),
+            SampleFormat::I64 => self._start::<i64, U>(&config.into(), provider, errors),
+            SampleFormat::F32 => self._start::<f32, U>(&config.into(), provider, errors),
+            SampleFormat::F64 => self._start::<f64, U>(&config.into(), provider, errors),
+            _ => return Err(EngineError::Unsupported),
+        }?;
+
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.stream.take();
+    }
+
+    fn play(&mut self) -> Result<(), EngineError> {
+        if let Some(stream) = self.stream.as_ref() {
+            stream.play()?;
+        }
+
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), EngineError> {
+        if let Some(stream) = self.stream.as_ref() {
+            stream.pause()?;
+        }
+
+        Ok(())
+    }
+
+    // Keep to the configured device first, then fall back to the default one.
+    fn reopen(&mut self, fallback: bool) -> Result<(), EngineError> {
+        let name = if fallback { None } else { self.name.as_deref() };
+        self.stream.take();
+        self.device = find_device(name)?;
+
+        Ok(())
+    }
+}
+
+fn find_device(name: Option<&str>) -> Result<Device, EngineError> {
+    match name {
+        Some(name) => device::find(Some(name)).ok_or_else(|| EngineError::NotFound(name.into())),
+        None => device::find(None).ok_or(EngineError::Unsupported),
+    }
+}
@@ -0,0 +1,130 @@
+use super::Output;
+use crate::buf::{Buf, Layout, Seq, Spec};
+use crate::engine::EngineError;
+use crate::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const PERIOD: usize = 1024;
+
+pub struct Null {
+    spec: Spec,
+    realtime: bool,
+    playing: Arc<AtomicBool>,
+    worker: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl Null {
+    pub fn new(layout: Layout, rate: u32) -> Self {
+        Self::with(layout, rate, true)
+    }
+
+    pub fn offline(layout: Layout, rate: u32) -> Self {
+        Self::with(layout, rate, false)
+    }
+
+    fn with(layout: Layout, rate: u32, realtime: bool) -> Self {
+        Self {
+            spec: Spec::new(PERIOD, layout, rate),
+            realtime,
+            playing: Arc::new(AtomicBool::new(false)),
+            worker: None,
+        }
+    }
+
+    pub fn spec(&self) -> Spec {
+        self.spec
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        self.realtime
+    }
+
+    fn join(&mut self) {
+        if let Some((stop, handle)) = self.worker.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Default for Null {
+    fn default() -> Self {
+        Self::new(Layout::STEREO, 48000)
+    }
+}
+
+impl<U> Output<U> for Null
+where
+    U: 'static + Write<Item = f32> + Send,
+{
+    fn name(&self) -> Option<String> {
+        Some("null".into())
+    }
+
+    fn start(&mut self, provider: U, _: Sender<EngineError>) -> Result<(), EngineError> {
+        self.join();
+
+        let spec = self.spec;
+        let realtime = self.realtime;
+        let playing = self.playing.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let mut provider = provider;
+            let mut buf = Seq::<f32>::with_spec(spec);
+            let period = Duration::from_secs_f64(spec.frames() as f64 / spec.rate() as f64);
+            let mut next = Instant::now();
+
+            while !flag.load(Ordering::Relaxed) {
+                let active = playing.load(Ordering::Relaxed);
+
+                if active {
+                    buf.set_pos(0);
+                    buf.set_len(0);
+                    provider.write_all(&mut buf);
+                    provider.set_latency(period);
+                }
+
+                if realtime {
+                    next += period;
+                    let now = Instant::now();
+
+                    match next.checked_duration_since(now) {
+                        Some(delay) => std::thread::sleep(delay),
+                        None => next = now,
+                    }
+                } else if !active {
+                    std::thread::sleep(period);
+                }
+            }
+        });
+
+        self.worker.replace((stop, handle));
+
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.join();
+    }
+
+    fn play(&mut self) -> Result<(), EngineError> {
+        self.playing.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), EngineError> {
+        self.playing.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Drop for Null {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
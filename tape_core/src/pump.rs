@@ -0,0 +1,468 @@
+use crate::buf::{self, proxy, Buf, BufMut, Layout, Seq, Spec};
+use crate::io::Write;
+use crate::ring::{self, Consumer, Producer};
+use cpal::{FromSample, Sample};
+use parking_lot::Mutex;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::debug;
+
+const CAPACITY: usize = 1 << 16;
+const CHUNK: usize = 512;
+const TARGET: Duration = Duration::from_millis(100);
+const IDLE: Duration = Duration::from_millis(10);
+
+pub struct Pump<U> {
+    provider: Arc<U>,
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<U> Pump<U>
+where
+    Arc<U>: 'static + Write<Item = f32> + Send + Sync,
+{
+    pub fn new(provider: Arc<U>) -> std::io::Result<Self> {
+        let (producer, samples) = ring::channel(CAPACITY);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(Some(TapState {
+                samples,
+                spec: None,
+                synced: false,
+                scratch: Vec::with_capacity(CAPACITY),
+            })),
+            format: AtomicU64::new(0),
+            epoch: AtomicUsize::new(0),
+            start: AtomicUsize::new(0),
+            marked: AtomicUsize::new(0),
+            flush: AtomicUsize::new(0),
+            flushed: AtomicBool::new(true),
+            requests: AtomicUsize::new(0),
+            latency: AtomicU64::new(0),
+            underruns: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+        });
+        let worker = {
+            let provider = provider.clone();
+            let shared = shared.clone();
+
+            std::thread::Builder::new()
+                .name("decoder".into())
+                .spawn(move || run::<U>(provider, shared, producer))?
+        };
+
+        let pump = Self {
+            provider,
+            shared,
+            worker: Some(worker),
+        };
+
+        Ok(pump)
+    }
+
+    /// Hands out the reading end of the pump, which comes back once the tap is dropped.
+    pub fn tap(&self) -> Option<Tap> {
+        let state = self.shared.state.lock().take()?;
+        let tap = Tap {
+            shared: self.shared.clone(),
+            state: ManuallyDrop::new(state),
+        };
+
+        Some(tap)
+    }
+
+    pub fn flush_with<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&Arc<U>) -> R,
+    {
+        let result = f(&self.provider);
+        self.flush();
+
+        result
+    }
+
+    /// Like `flush_with`, but buffered audio is only dropped when `f` returns true.
+    pub fn flush_if<F>(&mut self, f: F) -> bool
+    where
+        F: FnOnce(&Arc<U>) -> bool,
+    {
+        let flush = f(&self.provider);
+
+        if flush {
+            self.flush();
+        }
+
+        flush
+    }
+
+    fn flush(&self) {
+        // The decoder picks the request up between two chunks, so this never waits on decoding.
+        self.shared.requests.fetch_add(1, Ordering::Release);
+
+        if let Some(worker) = self.worker.as_ref() {
+            worker.thread().unpark();
+        }
+    }
+}
+
+impl<U> Drop for Pump<U> {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+
+        if let Some(worker) = self.worker.take() {
+            worker.thread().unpark();
+            let _ = worker.join();
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<Option<TapState>>,
+    format: AtomicU64,
+    epoch: AtomicUsize,
+    start: AtomicUsize,
+    marked: AtomicUsize,
+    flush: AtomicUsize,
+    flushed: AtomicBool,
+    requests: AtomicUsize,
+    latency: AtomicU64,
+    underruns: AtomicUsize,
+    stop: AtomicBool,
+}
+
+impl Shared {
+    fn output_format(&self) -> Option<(Layout, u32)> {
+        match self.format.load(Ordering::Acquire) {
+            0 => None,
+            format => Some((Layout::from_bits(format as u32), (format >> 32) as u32)),
+        }
+    }
+
+    fn latency(&self) -> Duration {
+        Duration::from_nanos(self.latency.load(Ordering::Relaxed))
+    }
+}
+
+/// The reading end of a pump, owned by the output so its callback never has to lock.
+pub struct Tap {
+    shared: Arc<Shared>,
+    state: ManuallyDrop<TapState>,
+}
+
+impl Tap {
+    pub fn output_format(&self) -> Option<(Layout, u32)> {
+        self.shared.output_format()
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.shared.latency()
+    }
+
+    pub fn underruns(&self) -> usize {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+}
+
+impl Write for Tap {
+    type Item = f32;
+
+    fn write<U>(&mut self, dst: &mut U) -> usize
+    where
+        U: BufMut,
+        U::Item: Sample + FromSample<Self::Item>,
+    {
+        let spec = dst.spec();
+        let p = dst.len();
+        let want = spec.frames() - p;
+        let shared = &*self.shared;
+        let state = &mut *self.state;
+
+        if !shared.flushed.swap(true, Ordering::Acquire) {
+            state.samples.skip_to(shared.flush.load(Ordering::Acquire));
+        }
+
+        let format = (spec.layout(), spec.rate());
+
+        if state.spec != Some(format) {
+            state.spec = Some(format);
+            state.synced = false;
+
+            let bits = (spec.rate() as u64) << 32 | spec.layout().bits() as u64;
+            shared.format.store(bits, Ordering::Release);
+            shared.epoch.fetch_add(1, Ordering::Release);
+        }
+
+        // Samples pushed before the decoder picked up the new format are dropped once it marks
+        // where the new ones start.
+        if !state.synced {
+            if shared.marked.load(Ordering::Acquire) != shared.epoch.load(Ordering::Relaxed) {
+                return silence(dst, p, want);
+            }
+
+            state.samples.skip_to(shared.start.load(Ordering::Acquire));
+            state.synced = true;
+        }
+
+        let channels = spec.channels();
+        let available = state.samples.len() / channels;
+        let n = std::cmp::min(available, want);
+
+        let scratch = &mut state.scratch;
+        scratch.clear();
+        state
+            .samples
+            .pop_with(n * channels, |sample| scratch.push(sample));
+
+        if n > 0 {
+            let mut src = proxy::int_mut(scratch, Spec::new(n, spec.layout(), spec.rate()));
+            src.set_len(n);
+            buf::copy(&src, dst);
+        }
+
+        if n < want {
+            shared.underruns.fetch_add(1, Ordering::Relaxed);
+            return n + silence(dst, p + n, want - n);
+        }
+
+        dst.set_len(p + n);
+
+        n
+    }
+
+    fn set_latency(&mut self, latency: Duration) {
+        let nanos = latency.as_nanos().min(u64::MAX as u128) as u64;
+        self.shared.latency.store(nanos, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Tap {
+    fn drop(&mut self) {
+        // Nothing touches the state after this, so it can go back to the pump for the next tap.
+        let state = unsafe { ManuallyDrop::take(&mut self.state) };
+        self.shared.state.lock().replace(state);
+    }
+}
+
+struct TapState {
+    samples: Consumer<f32>,
+    spec: Option<(Layout, u32)>,
+    synced: bool,
+    scratch: Vec<f32>,
+}
+
+fn run<U>(provider: Arc<U>, shared: Arc<Shared>, producer: Producer<f32>)
+where
+    Arc<U>: Write<Item = f32>,
+{
+    let mut provider = provider;
+    let mut producer = producer;
+    let mut buf: Option<Seq<f32>> = None;
+    let mut scratch = Vec::new();
+    let mut marked = 0;
+    let mut handled = 0;
+    let mut idle = false;
+
+    while !shared.stop.load(Ordering::Relaxed) {
+        let requests = shared.requests.load(Ordering::Acquire);
+
+        // Everything queued so far predates the latest command, so the output skips past it.
+        if requests != handled {
+            shared.flush.store(producer.tail(), Ordering::Release);
+            shared.flushed.store(false, Ordering::Release);
+            handled = requests;
+            idle = false;
+        }
+
+        let epoch = shared.epoch.load(Ordering::Acquire);
+        let Some((layout, rate)) = shared.output_format() else {
+            std::thread::park_timeout(IDLE);
+            continue;
+        };
+
+        if marked != epoch {
+            shared.start.store(producer.tail(), Ordering::Release);
+            shared.marked.store(epoch, Ordering::Release);
+            marked = epoch;
+        }
+        let channels = layout.count();
+        let target = (TARGET.as_secs_f64() * rate as f64) as usize * channels;
+        let target = std::cmp::min(target, producer.capacity());
+
+        if producer.len() + CHUNK * channels > target || idle {
+            idle = false;
+            std::thread::park_timeout(IDLE);
+            continue;
+        }
+
+        let spec = Spec::new(CHUNK, layout, rate);
+        let buf = match buf.as_mut() {
+            Some(buf) if buf.spec() == spec => buf,
+            _ => buf.insert(Seq::with_spec(spec)),
+        };
+
+        buf.set_pos(0);
+        buf.set_len(0);
+
+        if provider.write(buf) == 0 {
+            idle = true;
+            continue;
+        }
+
+        // A command changed the provider while this chunk was decoded, so it may be stale.
+        if shared.requests.load(Ordering::Acquire) != handled {
+            continue;
+        }
+
+        // The output switched formats while this chunk was decoded, so it is stale.
+        if shared.epoch.load(Ordering::Acquire) != epoch {
+            debug!("Output format changed, dropping decoded chunk");
+            continue;
+        }
+
+        let n = buf.len();
+        scratch.resize(n * channels, 0.0);
+        buf::copy(
+            buf,
+            &mut proxy::int_mut(&mut scratch, Spec::new(n, layout, rate)),
+        );
+        producer.push_slice(&scratch);
+
+        let buffered = producer.len() / channels;
+        let delay = Duration::from_secs_f64(buffered as f64 / rate as f64);
+        provider.set_latency(shared.latency() + delay);
+    }
+}
+
+fn silence<U>(dst: &mut U, p: usize, n: usize) -> usize
+where
+    U: BufMut,
+    U::Item: Sample,
+{
+    for mut frame in dst.frames_mut().skip(p).take(n) {
+        for sample in frame.iter_mut() {
+            *sample = U::Item::EQUILIBRIUM;
+        }
+    }
+
+    dst.set_len(p + n);
+
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+    use std::time::Instant;
+
+    struct Source {
+        level: AtomicU32,
+        delay: Duration,
+        busy: AtomicBool,
+    }
+
+    impl Source {
+        fn new(level: f32, delay: Duration) -> Arc<Self> {
+            Arc::new(Self {
+                level: AtomicU32::new(level.to_bits()),
+                delay,
+                busy: AtomicBool::new(false),
+            })
+        }
+
+        fn set_level(&self, level: f32) {
+            self.level.store(level.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    impl Write for Arc<Source> {
+        type Item = f32;
+
+        fn write<U>(&mut self, dst: &mut U) -> usize
+        where
+            U: BufMut,
+            U::Item: Sample + FromSample<Self::Item>,
+        {
+            self.busy.store(true, Ordering::Relaxed);
+            std::thread::sleep(self.delay);
+
+            let level = f32::from_bits(self.level.load(Ordering::Relaxed));
+            let p = dst.len();
+            let n = dst.spec().frames() - p;
+
+            for mut frame in dst.frames_mut().skip(p) {
+                for sample in frame.iter_mut() {
+                    *sample = U::Item::from_sample(level);
+                }
+            }
+
+            dst.set_len(p + n);
+
+            n
+        }
+    }
+
+    fn read(tap: &mut Tap) -> Vec<f32> {
+        let mut dst = Seq::with_spec(Spec::new(256, Layout::MONO, 48000));
+        tap.write(&mut dst);
+        dst.frames().map(|frame| frame.into_vec()[0]).collect()
+    }
+
+    #[test]
+    fn flush_does_not_wait_for_decoding() {
+        let source = Source::new(0.5, Duration::from_millis(500));
+        let mut pump = Pump::<Source>::new(source.clone()).unwrap();
+        let mut tap = pump.tap().unwrap();
+        read(&mut tap);
+
+        while !source.busy.load(Ordering::Relaxed) {
+            std::thread::sleep(IDLE);
+        }
+
+        let now = Instant::now();
+        pump.flush_with(|_| ());
+
+        assert!(now.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn flush_drops_stale_audio() {
+        let source = Source::new(0.25, Duration::from_millis(1));
+        let mut pump = Pump::<Source>::new(source).unwrap();
+        let mut tap = pump.tap().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        while !read(&mut tap).contains(&0.25) {
+            assert!(Instant::now() < deadline);
+        }
+
+        pump.flush_with(|source| source.set_level(0.75));
+
+        // Audio queued before the flush may still come out until the decoder catches up, but
+        // never once the new audio started.
+        let mut fresh = false;
+        let deadline = Instant::now() + Duration::from_secs(1);
+
+        while Instant::now() < deadline {
+            for sample in read(&mut tap) {
+                fresh |= sample == 0.75;
+                assert!(!fresh || sample != 0.25);
+            }
+        }
+
+        assert!(fresh);
+    }
+
+    #[test]
+    fn tap_comes_back() {
+        let pump = Pump::<Source>::new(Source::new(0.0, IDLE)).unwrap();
+        let tap = pump.tap().unwrap();
+
+        assert!(pump.tap().is_none());
+        drop(tap);
+        assert!(pump.tap().is_some());
+    }
+}
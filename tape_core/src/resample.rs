@@ -0,0 +1,564 @@
+use crate::buf::{Buf, BufMut, Seq, Spec};
+use crate::gain::{Normalize, ReplayGain};
+use crate::io::{Release, Seek, SeekFrom, Tell, Write};
+use crate::meta::{Describe, Metadata};
+use crate::mix::Matrix;
+use cpal::{FromSample, Sample};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::time::Duration;
+
+const CHUNK: usize = 1024;
+const PHASES: usize = 256;
+const SINC_TAPS: usize = 32;
+
+pub struct Resampler<T> {
+    inner: T,
+    quality: Quality,
+    kernel: Kernel,
+    buf: Option<Seq<f32>>,
+    window: Vec<f32>,
+    weights: Vec<f32>,
+    frame: Vec<f32>,
+    matrix: Option<Matrix>,
+    ratio: f64,
+    frac: f64,
+    drain: usize,
+    primed: bool,
+    done: bool,
+    finished: bool,
+    rates: Option<(u32, u32)>,
+}
+
+impl<T> Resampler<T> {
+    pub fn new(inner: T, quality: Quality) -> Self {
+        Self {
+            inner,
+            quality,
+            kernel: Kernel::Linear,
+            buf: None,
+            window: Vec::new(),
+            weights: Vec::new(),
+            frame: Vec::new(),
+            matrix: None,
+            ratio: 1.0,
+            frac: 0.0,
+            drain: 0,
+            primed: false,
+            done: false,
+            finished: false,
+            rates: None,
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// Takes effect at the next seek or track, so the current one plays on without a gap.
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.quality = quality;
+    }
+
+    fn reset(&mut self) {
+        // The buffered input is dropped here anyway, so this is where a new kernel takes over.
+        if self.rates.is_some() && self.kernel.quality() != self.quality {
+            self.kernel = Kernel::new(self.quality, self.ratio);
+        }
+
+        let taps = self.kernel.taps();
+        let channels = self.buf.as_ref().map_or(0, |buf| buf.spec().channels());
+
+        if let Some(buf) = self.buf.as_mut() {
+            buf.set_pos(0);
+            buf.set_len(0);
+        }
+
+        self.window.clear();
+        self.window.resize(taps * channels, 0.0);
+        self.weights.clear();
+        self.weights.resize(taps, 0.0);
+        self.frame.clear();
+        self.frame.resize(channels, 0.0);
+        self.frac = 0.0;
+        self.drain = taps / 2;
+        self.primed = false;
+        self.done = false;
+        self.finished = false;
+    }
+
+    fn configure(&mut self, src: Spec, rate: u32) {
+        let spec = Spec::new(CHUNK, src.layout(), src.rate());
+
+        self.ratio = src.rate() as f64 / rate as f64;
+        self.kernel = Kernel::new(self.quality, self.ratio);
+        self.buf = Some(Seq::with_spec(spec));
+        self.rates = Some((src.rate(), rate));
+        self.reset();
+    }
+}
+
+impl<T> Resampler<T>
+where
+    T: Write<Item = f32>,
+{
+    fn pull(&mut self) -> bool {
+        let Some(buf) = self.buf.as_mut() else {
+            return false;
+        };
+        let channels = buf.spec().channels();
+
+        if buf.is_empty() && !self.done {
+            buf.set_pos(0);
+            buf.set_len(0);
+
+            if self.inner.write(buf) == 0 {
+                self.done = true;
+            }
+        }
+
+        self.window.copy_within(channels.., 0);
+        let tail = self.window.len() - channels;
+
+        if self.done {
+            if self.drain == 0 {
+                self.finished = true;
+                return false;
+            }
+
+            self.window[tail..].fill(0.0);
+            self.drain -= 1;
+
+            return true;
+        }
+
+        let pos = buf.pos();
+
+        for (dst, src) in self.window[tail..].iter_mut().zip(buf.frame(pos).iter()) {
+            *dst = *src;
+        }
+
+        buf.set_pos(pos + 1);
+
+        true
+    }
+
+    fn prime(&mut self) {
+        let taps = self.kernel.taps();
+        self.primed = true;
+        self.pull();
+
+        if self.done {
+            self.finished = true;
+            return;
+        }
+
+        for _ in 0..taps / 2 {
+            self.pull();
+        }
+    }
+}
+
+impl<T> Write for Resampler<T>
+where
+    T: Write<Item = f32>,
+{
+    type Item = f32;
+
+    fn write<U>(&mut self, dst: &mut U) -> usize
+    where
+        U: BufMut,
+        U::Item: Sample + FromSample<Self::Item>,
+    {
+        let Some(src) = self.inner.format() else {
+            return self.inner.write(dst);
+        };
+        let rate = dst.spec().rate();
+
+        if src.rate() == rate {
+            self.rates = None;
+            return self.inner.write(dst);
+        }
+
+        let layout = self.buf.as_ref().map(|buf| buf.spec().layout());
+
+        if self.rates != Some((src.rate(), rate)) || layout != Some(src.layout()) {
+            self.configure(src, rate);
+        }
+
+        if !self.primed {
+            self.prime();
+        }
+
+        if self.finished {
+            return 0;
+        }
+
+        let channels = src.channels();
+        let layout = dst.spec().layout();
+        let matrix = match self.matrix.take() {
+            Some(matrix) if matrix.src() == src.layout() && matrix.dst() == layout => matrix,
+            _ => Matrix::new(src.layout(), layout),
+        };
+        let p = dst.len();
+        let mut n = 0;
+
+        for frame in dst.frames_mut().skip(p) {
+            self.kernel.weights(self.frac, &mut self.weights);
+
+            for (c, dst) in self.frame.iter_mut().enumerate() {
+                let mut acc = 0.0;
+
+                for (k, w) in self.weights.iter().enumerate() {
+                    acc += self.window[k * channels + c] * w;
+                }
+
+                *dst = acc;
+            }
+
+            matrix.mix::<f32, U::Item>(&self.frame, frame);
+
+            n += 1;
+            self.frac += self.ratio;
+
+            while self.frac >= 1.0 && !self.finished {
+                self.frac -= 1.0;
+                self.pull();
+            }
+
+            if self.finished {
+                break;
+            }
+        }
+
+        self.matrix = Some(matrix);
+        dst.set_len(p + n);
+
+        n
+    }
+}
+
+impl<T> Seek for Resampler<T>
+where
+    T: Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> bool {
+        let flag = self.inner.seek(pos);
+        self.reset();
+        flag
+    }
+}
+
+impl<T> Release for Resampler<T>
+where
+    T: Release,
+{
+    fn release(&mut self) {
+        self.inner.release();
+    }
+}
+
+impl<T> Describe for Resampler<T>
+where
+    T: Describe,
+{
+    fn metadata(&self) -> &Metadata {
+        self.inner.metadata()
+    }
+}
+
+impl<T> Tell for Resampler<T>
+where
+    T: Tell,
+{
+    fn position(&self) -> Duration {
+        let position = self.inner.position();
+        let (Some((rate, _)), Some(buf)) = (self.rates, self.buf.as_ref()) else {
+            return position;
+        };
+        let pending = buf.len() - buf.pos() + self.kernel.taps() / 2;
+
+        position.saturating_sub(Duration::from_secs_f64(pending as f64 / rate as f64))
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.inner.duration()
+    }
+}
+
+impl<T> Normalize for Resampler<T>
+where
+    T: Normalize,
+{
+    fn replay_gain(&self) -> ReplayGain {
+        self.inner.replay_gain()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Quality {
+    Low,
+    Medium,
+    High,
+}
+
+impl Quality {
+    pub fn get(&self) -> Self {
+        *self
+    }
+
+    pub fn set(&mut self, quality: Self) {
+        *self = quality;
+    }
+}
+
+enum Kernel {
+    Linear,
+    Cubic,
+    Sinc { taps: usize, table: Vec<f32> },
+}
+
+impl Kernel {
+    fn new(quality: Quality, ratio: f64) -> Self {
+        match quality {
+            Quality::Low => Self::Linear,
+            Quality::Medium => Self::Cubic,
+            Quality::High => Self::sinc(SINC_TAPS, ratio),
+        }
+    }
+
+    fn sinc(taps: usize, ratio: f64) -> Self {
+        let cutoff = f64::min(1.0, 1.0 / ratio);
+        let center = (taps / 2 - 1) as f64;
+        let mut table = Vec::with_capacity((PHASES + 1) * taps);
+
+        for phase in 0..=PHASES {
+            let t = phase as f64 / PHASES as f64;
+            let start = table.len();
+            let mut sum = 0.0;
+
+            for k in 0..taps {
+                let d = k as f64 - center - t;
+                let x = cutoff * d;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let w = (d + taps as f64 / 2.0) / taps as f64;
+                let blackman = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                let h = cutoff * sinc * blackman;
+
+                sum += h;
+                table.push(h as f32);
+            }
+
+            for h in &mut table[start..] {
+                *h /= sum as f32;
+            }
+        }
+
+        Self::Sinc { taps, table }
+    }
+
+    fn quality(&self) -> Quality {
+        match self {
+            Self::Linear => Quality::Low,
+            Self::Cubic => Quality::Medium,
+            Self::Sinc { .. } => Quality::High,
+        }
+    }
+
+    fn taps(&self) -> usize {
+        match self {
+            Self::Linear => 2,
+            Self::Cubic => 4,
+            Self::Sinc { taps, .. } => *taps,
+        }
+    }
+
+    fn weights(&self, t: f64, dst: &mut [f32]) {
+        match self {
+            Self::Linear => {
+                let t = t as f32;
+                dst[0] = 1.0 - t;
+                dst[1] = t;
+            }
+            Self::Cubic => {
+                let t = t as f32;
+                let t2 = t * t;
+                let t3 = t2 * t;
+                dst[0] = (-t3 + 2.0 * t2 - t) / 2.0;
+                dst[1] = (3.0 * t3 - 5.0 * t2 + 2.0) / 2.0;
+                dst[2] = (-3.0 * t3 + 4.0 * t2 + t) / 2.0;
+                dst[3] = (t3 - t2) / 2.0;
+            }
+            Self::Sinc { taps, table } => {
+                let x = t * PHASES as f64;
+                let i = std::cmp::min(x as usize, PHASES - 1);
+                let f = (x - i as f64) as f32;
+                let a = &table[i * taps..(i + 1) * taps];
+                let b = &table[(i + 1) * taps..(i + 2) * taps];
+
+                for ((dst, a), b) in dst.iter_mut().zip(a).zip(b) {
+                    *dst = a + (b - a) * f;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buf::Layout;
+
+    fn source(frames: usize, layout: Layout, rate: u32, f: impl Fn(usize) -> f32) -> Seq<f32> {
+        let mut seq = Seq::with_spec(Spec::new(frames, layout, rate));
+
+        for (i, mut frame) in seq.frames_mut().enumerate() {
+            for sample in frame.iter_mut() {
+                *sample = f(i);
+            }
+        }
+
+        seq.set_len(frames);
+        seq
+    }
+
+    fn render<T>(src: &mut T, layout: Layout, rate: u32) -> Vec<Vec<f32>>
+    where
+        T: Write<Item = f32>,
+    {
+        let mut dst = Seq::with_spec(Spec::new(512, layout, rate));
+        let mut frames = Vec::new();
+
+        loop {
+            dst.set_pos(0);
+            dst.set_len(0);
+
+            let n = src.write(&mut dst);
+
+            if n == 0 {
+                return frames;
+            }
+
+            frames.extend(dst.frames().take(n).map(|frame| frame.into_vec()));
+        }
+    }
+
+    #[test]
+    fn passthrough() {
+        let src = source(1000, Layout::STEREO, 48000, |i| i as f32 / 1000.0);
+        let mut resampler = Resampler::new(src, Quality::High);
+        let out = render(&mut resampler, Layout::STEREO, 48000);
+
+        assert_eq!(out.len(), 1000);
+        assert_eq!(out[500], [0.5, 0.5]);
+    }
+
+    #[test]
+    fn length_and_level() {
+        for quality in [Quality::Low, Quality::Medium, Quality::High] {
+            for (from, to) in [(44100, 48000), (48000, 44100), (22050, 96000)] {
+                let src = source(from as usize, Layout::STEREO, from, |_| 0.5);
+                let mut resampler = Resampler::new(src, quality);
+                let out = render(&mut resampler, Layout::STEREO, to);
+                let expected = to as usize;
+                let edge = SINC_TAPS * (to / from + 1) as usize;
+
+                assert!(out.len().abs_diff(expected) <= SINC_TAPS, "{from} -> {to}");
+
+                // Away from the edges a constant signal comes out unchanged.
+                for frame in &out[edge..expected - edge] {
+                    for sample in frame {
+                        assert!((sample - 0.5).abs() < 1e-3, "{from} -> {to}: {sample}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn preserves_frequency() {
+        let tone = |rate: u32| move |i: usize| (2.0 * PI * 440.0 * i as f64 / rate as f64).sin();
+        let src = source(44100, Layout::MONO, 44100, |i| tone(44100)(i) as f32);
+        let mut resampler = Resampler::new(src, Quality::High);
+        let out = render(&mut resampler, Layout::MONO, 48000);
+
+        let error = out[SINC_TAPS..40000]
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| (frame[0] as f64 - tone(48000)(i + SINC_TAPS)).abs())
+            .fold(0.0, f64::max);
+
+        // The kernel may shift the tone by about a sample, which the tolerance allows for.
+        assert!(error < 0.1, "{error}");
+    }
+
+    #[test]
+    fn remixes_channels() {
+        let src = source(4410, Layout::MONO, 44100, |_| 0.25);
+        let mut resampler = Resampler::new(src, Quality::Medium);
+        let out = render(&mut resampler, Layout::STEREO, 48000);
+
+        assert!(out[100..4000].iter().all(
+            |frame| frame.len() == 2 && frame.iter().all(|sample| (sample - 0.25).abs() < 1e-3)
+        ));
+    }
+
+    #[test]
+    fn seek_resets() {
+        let src = source(4410, Layout::MONO, 44100, |_| 0.25);
+        let mut resampler = Resampler::new(src, Quality::Low);
+        let first = render(&mut resampler, Layout::MONO, 48000);
+
+        resampler.inner_mut().set_pos(0);
+        resampler.reset();
+        let second = render(&mut resampler, Layout::MONO, 48000);
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn quality_waits_for_reset() {
+        let tone = |i: usize| ((i % 50) as f32 / 50.0 - 0.5) * 0.5;
+        let expected = |quality| {
+            let mut resampler = Resampler::new(source(4410, Layout::MONO, 44100, tone), quality);
+            render(&mut resampler, Layout::MONO, 48000)
+        };
+
+        let src = source(4410, Layout::MONO, 44100, tone);
+        let mut resampler = Resampler::new(src, Quality::High);
+        let mut dst = Seq::with_spec(Spec::new(512, Layout::MONO, 48000));
+        let n = resampler.write(&mut dst);
+
+        resampler.set_quality(Quality::Low);
+        let mut out = dst
+            .frames()
+            .take(n)
+            .map(|frame| frame.into_vec())
+            .collect::<Vec<_>>();
+        out.extend(render(&mut resampler, Layout::MONO, 48000));
+
+        assert_eq!(out, expected(Quality::High));
+
+        resampler.inner_mut().set_pos(0);
+        resampler.reset();
+
+        assert_eq!(
+            render(&mut resampler, Layout::MONO, 48000),
+            expected(Quality::Low)
+        );
+    }
+}
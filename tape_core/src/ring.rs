@@ -0,0 +1,174 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>)
+where
+    T: Copy,
+{
+    let capacity = capacity.next_power_of_two();
+    let buf = (0..capacity)
+        .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+        .collect();
+    let shared = Arc::new(Shared {
+        buf,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    let producer = Producer {
+        shared: shared.clone(),
+    };
+    let consumer = Consumer { shared };
+
+    (producer, consumer)
+}
+
+struct Shared<T> {
+    buf: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// Slots between `head` and `tail` belong to the consumer, all others to the producer, so a slot is
+// never accessed from both sides at once.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.buf[index & (self.capacity() - 1)].get()
+    }
+}
+
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Producer<T>
+where
+    T: Copy,
+{
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.shared.head.load(Ordering::Acquire);
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+
+        tail.wrapping_sub(head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn free(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    pub fn tail(&self) -> usize {
+        self.shared.tail.load(Ordering::Relaxed)
+    }
+
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        match self.push_slice(&[value]) {
+            0 => Err(value),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn push_slice(&mut self, src: &[T]) -> usize {
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        let n = std::cmp::min(src.len(), self.free());
+
+        for (i, value) in src[..n].iter().enumerate() {
+            unsafe { (*self.shared.slot(tail.wrapping_add(i))).write(*value) };
+        }
+
+        self.shared
+            .tail
+            .store(tail.wrapping_add(n), Ordering::Release);
+
+        n
+    }
+}
+
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Consumer<T>
+where
+    T: Copy,
+{
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let tail = self.shared.tail.load(Ordering::Acquire);
+
+        tail.wrapping_sub(head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let mut value = None;
+        self.pop_with(1, |v| value = Some(v));
+        value
+    }
+
+    pub fn pop_with<F>(&mut self, n: usize, mut f: F) -> usize
+    where
+        F: FnMut(T),
+    {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let n = std::cmp::min(n, self.len());
+
+        for i in 0..n {
+            f(unsafe { (*self.shared.slot(head.wrapping_add(i))).assume_init() });
+        }
+
+        self.shared
+            .head
+            .store(head.wrapping_add(n), Ordering::Release);
+
+        n
+    }
+
+    pub fn skip(&mut self, n: usize) -> usize {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let n = std::cmp::min(n, self.len());
+
+        self.shared
+            .head
+            .store(head.wrapping_add(n), Ordering::Release);
+
+        n
+    }
+
+    pub fn skip_to(&mut self, index: usize) -> usize {
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let n = index.wrapping_sub(head);
+
+        // Indices behind the consumer come out as huge distances.
+        if n > self.capacity() {
+            return 0;
+        }
+
+        self.skip(n)
+    }
+
+    pub fn clear(&mut self) -> usize {
+        self.skip(self.len())
+    }
+}
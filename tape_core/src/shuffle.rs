@@ -0,0 +1,369 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Shuffle {
+    Off,
+    Track,
+    Album,
+}
+
+impl Shuffle {
+    pub fn get(&self) -> Self {
+        *self
+    }
+
+    pub fn set(&mut self, shuffle: Self) {
+        *self = shuffle;
+    }
+}
+
+pub struct Order {
+    mode: Shuffle,
+    ranks: Vec<usize>,
+    inverse: Vec<Option<usize>>,
+}
+
+impl Order {
+    pub fn new() -> Self {
+        Self {
+            mode: Shuffle::Off,
+            ranks: Vec::new(),
+            inverse: Vec::new(),
+        }
+    }
+
+    pub fn mode(&self) -> Shuffle {
+        self.mode
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranks.is_empty()
+    }
+
+    pub fn get(&self, rank: usize) -> Option<usize> {
+        self.ranks.get(rank).copied()
+    }
+
+    pub fn rank(&self, index: usize) -> Option<usize> {
+        self.inverse.get(index).copied().flatten()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ranks.iter().copied()
+    }
+
+    pub fn generate(&mut self, mode: Shuffle, groups: Vec<Range<usize>>, current: Option<usize>) {
+        self.mode = mode;
+        self.ranks.clear();
+
+        let (mut groups, lead) = split(groups, current);
+        fastrand::shuffle(&mut groups);
+
+        // Playback carries on from the current entry, and the rest of its album follows it.
+        if let Some(lead) = lead {
+            self.ranks.extend(lead);
+        }
+
+        self.ranks.extend(groups.into_iter().flatten());
+        self.reindex();
+    }
+
+    pub fn cycle(&mut self, groups: Vec<Range<usize>>, current: usize) {
+        let (mut groups, lead) = split(groups, Some(current));
+
+        if let Some(lead) = lead.filter(|lead| lead.len() > 1) {
+            groups.push(lead.start + 1..lead.end);
+        }
+
+        fastrand::shuffle(&mut groups);
+
+        // The entry being played opens the new cycle so going back from there reaches it.
+        self.ranks.clear();
+        self.ranks.push(current);
+        self.ranks.extend(groups.into_iter().flatten());
+        self.reindex();
+    }
+
+    pub fn insert(&mut self, groups: Vec<Range<usize>>, after: Option<usize>) {
+        let start = after.map_or(0, |rank| rank + 1);
+
+        for group in groups {
+            let len = self.ranks.len();
+            let bounds = (start..=len)
+                .filter(|k| {
+                    *k == start
+                        || *k == len
+                        || self.mode != Shuffle::Album
+                        || self.ranks[*k] != self.ranks[*k - 1] + 1
+                })
+                .collect::<Vec<_>>();
+            let k = bounds[fastrand::usize(..bounds.len())];
+
+            self.ranks.splice(k..k, group);
+        }
+
+        self.reindex();
+    }
+
+    pub fn place(&mut self, groups: Vec<Range<usize>>, rank: usize) {
+        let rank = std::cmp::min(rank, self.ranks.len());
+        self.ranks.splice(rank..rank, groups.into_iter().flatten());
+        self.reindex();
+    }
+
+    /// Pulls the entries of each group that are still to be played together, at the slot of the
+    /// earliest one, for groups that were only known after the entries got ranked.
+    pub fn regroup(&mut self, groups: Vec<Range<usize>>, current: Option<usize>) {
+        let after = current
+            .and_then(|i| self.rank(i))
+            .map_or(0, |rank| rank + 1);
+        let pending = |i: &usize| self.rank(*i).is_some_and(|rank| rank >= after);
+        let (groups, lead) = split(groups, current);
+        let mut owner = vec![None; self.inverse.len()];
+        let mut ranks = self.ranks[..after].to_vec();
+
+        if let Some(lead) = lead {
+            ranks.extend((lead.start + 1..lead.end).filter(pending));
+        }
+
+        for (g, group) in groups.iter().enumerate() {
+            for slot in owner.iter_mut().take(group.end).skip(group.start) {
+                *slot = Some(g);
+            }
+        }
+
+        let mut seen = vec![false; owner.len()];
+
+        for i in ranks[after..].iter() {
+            seen[*i] = true;
+        }
+
+        for i in self.ranks[after..].iter().copied() {
+            if seen[i] {
+                continue;
+            }
+
+            let members = match owner[i] {
+                Some(g) => groups[g].clone().filter(pending).collect(),
+                None => vec![i],
+            };
+
+            for i in members {
+                seen[i] = true;
+                ranks.push(i);
+            }
+        }
+
+        self.ranks = ranks;
+        self.reindex();
+    }
+
+    pub fn remap<F>(&mut self, f: F)
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        self.ranks = self.ranks.iter().filter_map(|i| f(*i)).collect();
+        self.reindex();
+    }
+
+    pub fn clear(&mut self) {
+        self.mode = Shuffle::Off;
+        self.ranks.clear();
+        self.inverse.clear();
+    }
+
+    fn reindex(&mut self) {
+        let len = self.ranks.iter().max().map_or(0, |i| i + 1);
+        self.inverse.clear();
+        self.inverse.resize(len, None);
+
+        for (rank, i) in self.ranks.iter().enumerate() {
+            self.inverse[*i] = Some(rank);
+        }
+    }
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn split(
+    groups: Vec<Range<usize>>,
+    current: Option<usize>,
+) -> (Vec<Range<usize>>, Option<Range<usize>>) {
+    let mut rest = Vec::with_capacity(groups.len() + 1);
+    let mut lead = None;
+
+    for group in groups {
+        match current {
+            Some(current) if group.contains(&current) => {
+                if group.start < current {
+                    rest.push(group.start..current);
+                }
+
+                lead = Some(current..group.end);
+            }
+            _ => rest.push(group),
+        }
+    }
+
+    (rest, lead)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn singles(n: usize) -> Vec<Range<usize>> {
+        (0..n).map(|i| i..i + 1).collect()
+    }
+
+    fn album(range: Range<usize>) -> Vec<Range<usize>> {
+        std::iter::once(range).collect()
+    }
+
+    fn ranks(order: &Order) -> Vec<usize> {
+        order.iter().collect()
+    }
+
+    fn consistent(order: &Order) -> bool {
+        order
+            .iter()
+            .enumerate()
+            .all(|(rank, i)| order.rank(i) == Some(rank))
+    }
+
+    fn contiguous(ranks: &[usize], group: Range<usize>) -> bool {
+        let len = group.len();
+        let Some(p) = ranks.iter().position(|i| *i == group.start) else {
+            return false;
+        };
+
+        ranks[p..].iter().copied().take(len).eq(group)
+    }
+
+    #[test]
+    fn generate_leads_with_current() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Track, singles(16), Some(5));
+
+        let mut sorted = ranks(&order);
+        assert_eq!(sorted[0], 5);
+        sorted.sort();
+        assert_eq!(sorted, (0..16).collect::<Vec<_>>());
+        assert!(consistent(&order));
+    }
+
+    #[test]
+    fn generate_keeps_albums_together() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Album, vec![0..3, 3..4, 4..8], Some(1));
+
+        let ranks = ranks(&order);
+        assert_eq!(&ranks[..2], &[1, 2]);
+        assert!(contiguous(&ranks, 4..8));
+        assert!(consistent(&order));
+    }
+
+    #[test]
+    fn cycle_starts_at_current() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Album, vec![0..4, 4..8], None);
+        order.cycle(vec![0..4, 4..8], 1);
+
+        let ranks = ranks(&order);
+        assert_eq!(ranks.len(), 8);
+        assert_eq!(ranks[0], 1);
+        assert!(contiguous(&ranks, 4..8));
+        assert!(contiguous(&ranks, 2..4));
+        assert!(consistent(&order));
+    }
+
+    #[test]
+    fn insert_after_rank() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Album, album(0..4), Some(0));
+        order.insert(album(4..6), Some(3));
+
+        assert_eq!(ranks(&order), [0, 1, 2, 3, 4, 5]);
+        assert!(consistent(&order));
+    }
+
+    #[test]
+    fn insert_between_albums() {
+        // The slot is random, but it never falls inside an album.
+        for _ in 0..32 {
+            let mut order = Order::new();
+            order.generate(Shuffle::Album, vec![0..3, 3..6], Some(0));
+            order.insert(album(6..8), Some(0));
+
+            let ranks = ranks(&order);
+            assert!(contiguous(&ranks, 1..3));
+            assert!(contiguous(&ranks, 3..6));
+            assert!(contiguous(&ranks, 6..8));
+        }
+    }
+
+    #[test]
+    fn place_at_rank() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Off, album(0..3), Some(0));
+        order.place(album(3..5), 1);
+
+        assert_eq!(ranks(&order), [0, 3, 4, 1, 2]);
+        assert_eq!(order.rank(3), Some(1));
+        assert!(consistent(&order));
+    }
+
+    #[test]
+    fn regroup_after_probing() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Album, singles(8), Some(0));
+        order.regroup(vec![0..3, 3..4, 4..8], Some(0));
+
+        let ranks = ranks(&order);
+        assert_eq!(&ranks[..3], &[0, 1, 2]);
+        assert!(contiguous(&ranks, 4..8));
+        assert_eq!(ranks.len(), 8);
+        assert!(consistent(&order));
+    }
+
+    #[test]
+    fn regroup_keeps_played() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Album, singles(6), Some(2));
+        let played = order.get(0);
+        let current = order.get(1).unwrap();
+        order.regroup(album(0..6), Some(current));
+
+        let ranks = ranks(&order);
+        assert_eq!(ranks.first().copied(), played);
+        assert_eq!(ranks[1], current);
+        assert_eq!(ranks.len(), 6);
+        assert!(consistent(&order));
+    }
+
+    #[test]
+    fn remap_drops_removed() {
+        let mut order = Order::new();
+        order.generate(Shuffle::Off, album(0..5), Some(0));
+        order.remap(|i| match i {
+            2 => None,
+            i if i > 2 => Some(i - 1),
+            i => Some(i),
+        });
+
+        assert_eq!(ranks(&order), [0, 1, 2, 3]);
+        assert_eq!(order.rank(4), None);
+        assert!(consistent(&order));
+    }
+}
@@ -1,18 +1,23 @@
-use crate::buf::{Buf, BufMut, Seq, Spec};
-use crate::io::{Seek, Write};
+use crate::buf::{Buf, BufMut, Layout, Seq, Spec};
+use crate::gain::{Normalization, Normalize, ReplayGain};
+use crate::io::{Release, Seek, SeekFrom, Tell, Write};
+use crate::meta::{Describe, Metadata};
+use crate::mix::Matrix;
 use cpal::{FromSample, Sample};
-use symphonia::audio::{AudioBuffer, AudioBufferRef};
+use std::time::Duration;
+use symphonia::audio::{AudioBuffer, AudioBufferRef, Signal};
 use symphonia::codecs::{CodecParameters, Decoder, DecoderOptions};
 use symphonia::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
 use symphonia::io::{MediaSource, MediaSourceStream, MediaSourceStreamOptions};
-use symphonia::meta::MetadataOptions;
+use symphonia::meta::{MetadataOptions, StandardTagKey, Tag, Value, Visual};
 use symphonia::probe::{Hint, ProbeResult};
-use symphonia::units::Time;
+use symphonia::units::{Time, TimeBase};
 use thiserror::Error;
 
 pub struct Sound {
     reader: SoundReader,
     buf: Seq<f32>,
+    matrix: Option<Matrix>,
 }
 
 impl Sound {
@@ -28,15 +33,69 @@ impl Sound {
 
         let src = reader.decoder.last_decoded();
         let frames = src.capacity();
-        let channels = src.spec().channels.count();
-        let spec = Spec::new(frames, channels);
+        let layout = read_layout(&src)?;
+        let rate = src.spec().rate;
+        let spec = Spec::new(frames, layout, rate);
         let mut buf = Seq::with_spec(spec);
         export_data(&src, &mut buf)?;
 
-        let sound = Self { reader, buf };
+        let sound = Self {
+            reader,
+            buf,
+            matrix: None,
+        };
 
         Ok(sound)
     }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.reader.metadata
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        let time_base = self.reader.time_base?;
+        let n_frames = self.reader.n_frames?;
+
+        Some(to_duration(time_base.calc_time(n_frames)))
+    }
+
+    pub fn position(&self) -> Duration {
+        let ts = self.reader.ts.saturating_sub(self.reader.start_ts);
+        let start = match self.reader.time_base {
+            Some(time_base) => to_duration(time_base.calc_time(ts)),
+            None => Duration::ZERO,
+        };
+        let offset = self.buf.pos() as f64 / self.buf.spec().rate() as f64;
+
+        start + Duration::from_secs_f64(offset)
+    }
+
+    fn fill(&mut self) -> bool {
+        while self.buf.is_empty() {
+            if !self.reader.advance() {
+                return false;
+            }
+
+            let src = self.reader.decoder.last_decoded();
+            let layout = match read_layout(&src) {
+                Ok(layout) => layout,
+                Err(_) => return false,
+            };
+            let rate = src.spec().rate;
+            let spec = self.buf.spec();
+
+            if src.capacity() > spec.frames() || layout != spec.layout() || rate != spec.rate() {
+                let frames = std::cmp::max(src.capacity(), spec.frames());
+                self.buf = Seq::with_spec(Spec::new(frames, layout, rate));
+            }
+
+            if export_data(&src, &mut self.buf).is_err() {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 impl Write for Sound {
@@ -47,28 +106,25 @@ impl Write for Sound {
         U: BufMut,
         U::Item: Sample + FromSample<Self::Item>,
     {
-        if self.buf.is_empty() {
-            if !self.reader.advance() {
-                return 0;
-            }
-
-            let src = self.reader.decoder.last_decoded();
-
-            if export_data(&src, &mut self.buf).is_err() {
-                return 0;
-            };
+        if !self.fill() {
+            return 0;
         }
 
         let p1 = self.buf.pos();
         let p2 = dst.len();
+        let layout = self.buf.spec().layout();
         let mut n = 0;
 
-        for (src, mut dst) in self.buf.frames().skip(p1).zip(dst.frames_mut().skip(p2)) {
-            for (src, dst) in src.iter().zip(dst.iter_mut()) {
-                *dst = U::Item::from_sample(*src);
-            }
+        if layout == dst.spec().layout() {
+            n = crate::buf::copy(&self.buf, dst);
+        } else {
+            let matrix = Matrix::cached(&mut self.matrix, layout, dst.spec().layout());
+            let frames = self.buf.frames().take(self.buf.len()).skip(p1);
 
-            n += 1;
+            for (src, dst) in frames.zip(dst.frames_mut().skip(p2)) {
+                matrix.apply(src, dst);
+                n += 1;
+            }
         }
 
         self.buf.set_pos(p1 + n);
@@ -76,28 +132,95 @@ impl Write for Sound {
 
         n
     }
+
+    fn format(&self) -> Option<Spec> {
+        Some(self.buf.spec())
+    }
 }
 
+impl Tell for Sound {
+    fn position(&self) -> Duration {
+        Sound::position(self)
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        Sound::duration(self)
+    }
+}
+
+impl Describe for Sound {
+    fn metadata(&self) -> &Metadata {
+        Sound::metadata(self)
+    }
+}
+
+impl Normalize for Sound {
+    fn replay_gain(&self) -> ReplayGain {
+        self.reader.replay_gain
+    }
+}
+
+impl Release for Sound {}
+
 impl Seek for Sound {
-    fn seek(&mut self, t: u64) -> bool {
+    fn seek(&mut self, pos: SeekFrom) -> bool {
+        let Some(t) = pos.resolve(self.position(), self.duration()) else {
+            return false;
+        };
         let seek = self.reader.demuxer.seek(
             SeekMode::Accurate,
             SeekTo::Time {
                 time: Time {
-                    seconds: t,
-                    frac: 0.0,
+                    seconds: t.as_secs(),
+                    frac: t.subsec_nanos() as f64 / 1e9,
                 },
-                track_id: None,
+                track_id: Some(self.reader.track_id),
             },
         );
+        let seeked = match seek {
+            Ok(seeked) => seeked,
+            Err(_) => return false,
+        };
+
+        self.reader.decoder.reset();
+        self.reader.ts = seeked.actual_ts;
+        self.buf.set_pos(0);
+        self.buf.set_len(0);
+
+        // The demuxer may land on an earlier packet, so decode up to the requested timestamp.
+        while self.fill() {
+            let ahead = seeked.required_ts.saturating_sub(self.reader.ts);
+            let skip = match self.reader.time_base {
+                Some(time_base) => {
+                    let ahead = to_duration(time_base.calc_time(ahead));
+                    (ahead.as_secs_f64() * self.buf.spec().rate() as f64) as usize
+                }
+                None => 0,
+            };
+
+            if skip < self.buf.len() {
+                self.buf.set_pos(skip);
+                return true;
+            }
+
+            self.buf.set_pos(self.buf.len());
+        }
 
-        seek.is_ok()
+        // Running out of packets means the position lies past the last decodable frame.
+        false
     }
 }
 
 struct SoundReader {
     demuxer: Box<dyn FormatReader>,
     decoder: Box<dyn Decoder>,
+    metadata: Metadata,
+    replay_gain: ReplayGain,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    n_frames: Option<u64>,
+    start_ts: u64,
+    ts: u64,
 }
 
 impl SoundReader {
@@ -105,11 +228,37 @@ impl SoundReader {
     where
         T: 'static + MediaSource,
     {
-        let probe = make_probe(source)?;
-        let demuxer = probe.format;
+        let mut probe = make_probe(source)?;
+        let mut tags = Vec::new();
+        let mut visuals = Vec::new();
+
+        if let Some(metadata) = probe.metadata.get() {
+            read_revisions(metadata, &mut tags, &mut visuals);
+        }
+
+        let mut demuxer = probe.format;
+        read_revisions(demuxer.metadata(), &mut tags, &mut visuals);
+
+        let metadata = Metadata::read(&tags, &visuals);
+        let replay_gain = read_replay_gain(&tags);
+
         let track = demuxer.default_track().ok_or(SoundError::Invalid)?;
         let decoder = make_decoder(&track.codec_params)?;
-        let reader = Self { demuxer, decoder };
+        let track_id = track.id;
+        let time_base = track.codec_params.time_base;
+        let n_frames = track.codec_params.n_frames;
+        let start_ts = track.codec_params.start_ts;
+        let reader = Self {
+            demuxer,
+            decoder,
+            metadata,
+            replay_gain,
+            track_id,
+            time_base,
+            n_frames,
+            start_ts,
+            ts: start_ts,
+        };
 
         Ok(reader)
     }
@@ -121,6 +270,12 @@ impl SoundReader {
                 Err(_) => return false,
             };
 
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            self.ts = packet.ts();
+
             if let Err(e) = self.decoder.decode(&packet) {
                 match e {
                     symphonia::Error::DecodeError(_) | symphonia::Error::IoError(_) => continue,
@@ -133,6 +288,84 @@ impl SoundReader {
     }
 }
 
+fn read_replay_gain(tags: &[Tag]) -> ReplayGain {
+    let mut track = (None, None);
+    let mut album = (None, None);
+
+    for tag in tags {
+        let value = match read_number(&tag.value) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        match tag.std_key {
+            Some(StandardTagKey::ReplayGainTrackGain) => track.0 = Some(value),
+            Some(StandardTagKey::ReplayGainTrackPeak) => track.1 = Some(value),
+            Some(StandardTagKey::ReplayGainAlbumGain) => album.0 = Some(value),
+            Some(StandardTagKey::ReplayGainAlbumPeak) => album.1 = Some(value),
+            _ if tag.key.eq_ignore_ascii_case("R128_TRACK_GAIN") => {
+                track.0 = track.0.or(Some(r128_to_replay_gain(value)))
+            }
+            _ if tag.key.eq_ignore_ascii_case("R128_ALBUM_GAIN") => {
+                album.0 = album.0.or(Some(r128_to_replay_gain(value)))
+            }
+            _ => (),
+        }
+    }
+
+    let normalization =
+        |(gain, peak): (Option<f32>, Option<f32>)| gain.map(|gain| Normalization { gain, peak });
+
+    ReplayGain {
+        track: normalization(track),
+        album: normalization(album),
+    }
+}
+
+fn read_revisions(
+    mut metadata: symphonia::meta::Metadata<'_>,
+    tags: &mut Vec<Tag>,
+    visuals: &mut Vec<Visual>,
+) {
+    loop {
+        if let Some(rev) = metadata.current() {
+            tags.extend_from_slice(rev.tags());
+            visuals.extend_from_slice(rev.visuals());
+        }
+
+        if metadata.pop().is_none() {
+            break;
+        }
+    }
+}
+
+fn read_number(value: &Value) -> Option<f32> {
+    match value {
+        Value::Float(value) => Some(*value as f32),
+        Value::SignedInt(value) => Some(*value as f32),
+        Value::UnsignedInt(value) => Some(*value as f32),
+        Value::String(value) => {
+            let value = value.trim();
+            let value = value
+                .strip_suffix("dB")
+                .or_else(|| value.strip_suffix("db"))
+                .unwrap_or(value);
+
+            value.trim().parse().ok()
+        }
+        _ => None,
+    }
+}
+
+// R128 gains are Q7.8 fixed-point values relative to -23 LUFS, whereas ReplayGain targets -18 LUFS.
+fn r128_to_replay_gain(value: f32) -> f32 {
+    value / 256.0 + 5.0
+}
+
+fn to_duration(time: Time) -> Duration {
+    Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+}
+
 fn make_probe<T>(source: T) -> Result<ProbeResult, SoundError>
 where
     T: 'static + MediaSource,
@@ -148,7 +381,13 @@ where
 
     symphonia::default::get_probe()
         .format(&hint, source, &format_options, &metadata_options)
-        .map_err(|_| SoundError::Unsupported)
+        .map_err(|e| match e {
+            // A file cut short fails with an I/O error too, but it is just as unreadable.
+            symphonia::Error::IoError(e) if e.kind() != std::io::ErrorKind::UnexpectedEof => {
+                SoundError::Io(e)
+            }
+            _ => SoundError::Unsupported,
+        })
 }
 
 fn make_decoder(codec_params: &CodecParameters) -> Result<Box<dyn Decoder>, SoundError> {
@@ -181,24 +420,56 @@ where
         AudioBufferRef::S32(src) => _export_data(src, dst),
         AudioBufferRef::F32(src) => _export_data(src, dst),
         AudioBufferRef::F64(src) => _export_data(src, dst),
-        _ => return Err(SoundError::Unsupported),
-    };
-    Ok(())
+        _ => Err(SoundError::Unsupported),
+    }
 }
 
-fn _export_data<U, T>(src: &AudioBuffer<T>, dst: &mut U)
+fn _export_data<U, T>(src: &AudioBuffer<T>, dst: &mut U) -> Result<(), SoundError>
 where
     T: Sample + symphonia::sample::Sample,
     U: BufMut,
     U::Item: Sample + FromSample<T>,
 {
-    let frames = src.capacity();
-    let channels = src.spec().channels.count();
-    let spec = Spec::new(frames, channels);
+    let frames = src.frames();
+    let layout = layout_of(src.spec().channels.bits(), src.planes().planes().len())?;
+    let rate = src.spec().rate;
 
     dst.set_pos(0);
     dst.set_len(0);
-    crate::buf::proxy::dy(src.planes().planes(), spec).write_all(dst);
+
+    if frames == 0 {
+        return Ok(());
+    }
+
+    let spec = Spec::new(frames, layout, rate);
+    crate::buf::proxy::dy(src.planes().planes(), spec).write(dst);
+
+    Ok(())
+}
+
+fn read_layout(src: &AudioBufferRef) -> Result<Layout, SoundError> {
+    let planes = match src {
+        AudioBufferRef::U8(src) => src.planes().planes().len(),
+        AudioBufferRef::U16(src) => src.planes().planes().len(),
+        AudioBufferRef::U32(src) => src.planes().planes().len(),
+        AudioBufferRef::S8(src) => src.planes().planes().len(),
+        AudioBufferRef::S16(src) => src.planes().planes().len(),
+        AudioBufferRef::S32(src) => src.planes().planes().len(),
+        AudioBufferRef::F32(src) => src.planes().planes().len(),
+        AudioBufferRef::F64(src) => src.planes().planes().len(),
+        _ => return Err(SoundError::Unsupported),
+    };
+
+    layout_of(src.spec().channels.bits(), planes)
+}
+
+// Some decoders leave the channel mask empty, the plane count still tells the layout apart.
+fn layout_of(bits: u32, planes: usize) -> Result<Layout, SoundError> {
+    match bits {
+        0 if planes > 0 => Ok(Layout::with_count(planes)),
+        0 => Err(SoundError::Unsupported),
+        bits => Ok(Layout::from_bits(bits)),
+    }
 }
 
 #[derive(Error, Debug)]
@@ -207,4 +478,6 @@ pub enum SoundError {
     Unsupported,
     #[error("invalid media container")]
     Invalid,
+    #[error(transparent)]
+    Io(std::io::Error),
 }
@@ -0,0 +1,162 @@
+use std::io::Cursor;
+use std::path::Path;
+use tape_core::buf::{Buf, BufMut, Layout, Seq, Spec};
+use tape_core::export::{self, ExportError, Flac, SampleFormat, Wav};
+use tape_core::io::Write;
+use tape_core::Sound;
+
+const RATE: u32 = 8000;
+
+// Integer patterns keep the golden files independent of the platform's maths library.
+fn signal(frames: usize, layout: Layout) -> Seq<f32> {
+    let mut seq = Seq::with_spec(Spec::new(frames, layout, RATE));
+
+    for (i, mut frame) in seq.frames_mut().enumerate() {
+        for (c, sample) in frame.iter_mut().enumerate() {
+            *sample = match c % 3 {
+                0 => ((i * 37) % 200) as f32 / 100.0 - 1.0,
+                1 => [0.5, -0.5][(i / 16) % 2] * (frames - i) as f32 / frames as f32,
+                _ => ((i * 7 + c) % 64) as f32 / 128.0 - 0.25,
+            };
+        }
+    }
+
+    seq.set_len(frames);
+    seq
+}
+
+fn encode<E>(src: &mut Seq<f32>, mut encoder: E, limit: Option<u64>) -> (u64, E)
+where
+    E: export::Encode,
+{
+    let layout = src.spec().layout();
+    let frames = export::render(src, &mut encoder, layout, RATE, limit).unwrap();
+    (frames, encoder)
+}
+
+fn wav(format: SampleFormat, frames: usize, layout: Layout) -> Vec<u8> {
+    let mut src = signal(frames, layout);
+    let wav = Wav::new(Cursor::new(Vec::new()), format, layout, RATE).unwrap();
+    let (n, wav) = encode(&mut src, wav, None);
+    assert_eq!(n, frames as u64);
+    wav.into_inner().into_inner()
+}
+
+fn flac(format: SampleFormat, frames: usize, layout: Layout) -> Vec<u8> {
+    let mut src = signal(frames, layout);
+    let flac = Flac::new(Cursor::new(Vec::new()), format, layout, RATE).unwrap();
+    let (n, flac) = encode(&mut src, flac, None);
+    assert_eq!(n, frames as u64);
+    flac.into_inner().into_inner()
+}
+
+fn decode(data: Vec<u8>, layout: Layout) -> Vec<Vec<f32>> {
+    let mut sound = Sound::new(Cursor::new(data)).unwrap();
+    let mut dst = Seq::with_spec(Spec::new(1024, layout, RATE));
+    let mut frames = Vec::new();
+
+    loop {
+        dst.set_pos(0);
+        dst.set_len(0);
+
+        match sound.write(&mut dst) {
+            0 => return frames,
+            n => frames.extend(dst.frames().take(n).map(|frame| frame.into_vec())),
+        }
+    }
+}
+
+// Set TAPE_BLESS to rewrite the golden files after an intended change to an encoder.
+fn golden(name: &str, data: &[u8]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name);
+
+    if std::env::var_os("TAPE_BLESS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, data).unwrap();
+    }
+
+    let expected = std::fs::read(&path).unwrap();
+    assert!(data == expected, "{name}: output differs from golden file");
+}
+
+fn round_trip(data: Vec<u8>, frames: usize, layout: Layout, bits: u32) {
+    let src = signal(frames, layout);
+    let decoded = decode(data, layout);
+    let step = 1.0 / (1u32 << (bits - 1)) as f32;
+
+    assert_eq!(decoded.len(), frames);
+
+    for (a, b) in src.frames().zip(&decoded) {
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() <= step, "{a} != {b}");
+        }
+    }
+}
+
+#[test]
+fn wav_s16() {
+    let data = wav(SampleFormat::S16, 256, Layout::STEREO);
+    golden("s16.wav", &data);
+    round_trip(data, 256, Layout::STEREO, 16);
+}
+
+#[test]
+fn wav_f32() {
+    let data = wav(SampleFormat::F32, 256, Layout::STEREO);
+    golden("f32.wav", &data);
+    round_trip(data, 256, Layout::STEREO, 24);
+}
+
+#[test]
+fn wav_s32_surround() {
+    let data = wav(SampleFormat::S32, 64, Layout::SURROUND_5_1);
+    golden("s32-5.1.wav", &data);
+    round_trip(data, 64, Layout::SURROUND_5_1, 32);
+}
+
+#[test]
+fn flac_s16() {
+    // Long enough to span more than one block.
+    let data = flac(SampleFormat::S16, 5000, Layout::STEREO);
+    golden("s16.flac", &data);
+    round_trip(data, 5000, Layout::STEREO, 16);
+}
+
+#[test]
+fn flac_s24_mono() {
+    let data = flac(SampleFormat::S24, 1000, Layout::MONO);
+    golden("s24-mono.flac", &data);
+    round_trip(data, 1000, Layout::MONO, 24);
+}
+
+#[test]
+fn flac_rejects_float() {
+    let flac = Flac::new(
+        Cursor::new(Vec::new()),
+        SampleFormat::F32,
+        Layout::STEREO,
+        RATE,
+    );
+    assert!(matches!(flac, Err(ExportError::Unsupported)));
+}
+
+#[test]
+fn render_limit() {
+    let mut src = signal(1000, Layout::STEREO);
+    let wav = Wav::new(
+        Cursor::new(Vec::new()),
+        SampleFormat::S16,
+        Layout::STEREO,
+        RATE,
+    )
+    .unwrap();
+    let (n, wav) = encode(&mut src, wav, Some(300));
+
+    assert_eq!(n, 300);
+    assert_eq!(
+        decode(wav.into_inner().into_inner(), Layout::STEREO).len(),
+        300
+    );
+}
@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tape_core::buf::{Buf, BufMut, Layout, Seq, Spec};
+use tape_core::engine::PlaybackState;
+use tape_core::event::Event;
+use tape_core::export::{self, SampleFormat, Wav};
+use tape_core::factory::Factory;
+use tape_core::io::{Seek, SeekFrom};
+use tape_core::output::Null;
+use tape_core::resample::Quality;
+use tape_core::{Engine, Entry, Gain, Sound};
+
+const RATE: u32 = 48000;
+
+struct Fixtures {
+    dir: PathBuf,
+}
+
+impl Fixtures {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("tape-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self { dir }
+    }
+
+    fn tone(&self, name: &str, frames: usize, layout: Layout) -> PathBuf {
+        let mut src = Seq::with_spec(Spec::new(frames, layout, RATE));
+
+        for (i, mut frame) in src.frames_mut().enumerate() {
+            for sample in frame.iter_mut() {
+                *sample = ((i % 100) as f32 / 100.0 - 0.5) * 0.5;
+            }
+        }
+
+        src.set_len(frames);
+
+        let path = self.dir.join(name);
+        let file = BufWriter::new(File::create(&path).unwrap());
+        let mut wav = Wav::new(file, SampleFormat::S16, layout, RATE).unwrap();
+        export::render(&mut src, &mut wav, layout, RATE, None).unwrap();
+        path
+    }
+
+    fn garbage(&self, name: &str) -> PathBuf {
+        let path = self.dir.join(name);
+        std::fs::write(&path, b"not audio at all").unwrap();
+        path
+    }
+}
+
+impl Drop for Fixtures {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn entries(paths: &[PathBuf]) -> Vec<Entry> {
+    paths
+        .iter()
+        .map(|path| Entry::new(path, Quality::High))
+        .collect()
+}
+
+fn render(paths: &[PathBuf], layout: Layout, rate: u32) -> u64 {
+    let factory = Factory::new();
+    factory.extend(entries(paths));
+
+    let mut provider = Arc::new(Gain::new(factory));
+    let mut wav = Wav::new(Cursor::new(Vec::new()), SampleFormat::S16, layout, rate).unwrap();
+    export::render(&mut provider, &mut wav, layout, rate, None).unwrap()
+}
+
+fn wait<F>(events: &Receiver<Event>, mut f: F) -> Vec<Event>
+where
+    F: FnMut(&Event) -> bool,
+{
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut seen = Vec::new();
+
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let event = events
+            .recv_timeout(timeout)
+            .expect("timed out waiting for event");
+        let done = f(&event);
+        seen.push(event);
+
+        if done {
+            return seen;
+        }
+    }
+}
+
+fn open(path: &Path) -> Sound {
+    Sound::new(File::open(path).unwrap()).unwrap()
+}
+
+#[test]
+fn plays_queue_through() {
+    let fixtures = Fixtures::new("play");
+    let paths = [
+        fixtures.tone("a.wav", 4800, Layout::STEREO),
+        fixtures.tone("b.wav", 2400, Layout::MONO),
+    ];
+
+    let provider = Gain::new(Factory::new());
+    let output = Box::new(Null::offline(Layout::STEREO, RATE));
+    let mut engine = Engine::<Gain<Factory<Entry>>>::with_output(provider, output).unwrap();
+    let (tx, events) = std::sync::mpsc::channel();
+    engine.provider().inner().set_events(tx.clone());
+    engine.set_events(tx);
+    engine.run().unwrap();
+
+    engine.flush_with(|provider| {
+        let factory = provider.inner();
+        factory.extend(entries(&paths));
+        assert!(factory.select(0));
+    });
+    engine.state().set(PlaybackState::Playing);
+
+    let seen = wait(&events, |event| matches!(event, Event::EndOfQueue));
+
+    assert!(seen
+        .iter()
+        .any(|event| matches!(event, Event::TrackChanged { index: 1 })));
+    assert!(seen.iter().any(|event| matches!(
+        event,
+        Event::StateChanged {
+            state: PlaybackState::Playing
+        }
+    )));
+}
+
+#[test]
+fn renders_queue() {
+    let fixtures = Fixtures::new("render");
+    let paths = [
+        fixtures.tone("a.wav", 4800, Layout::STEREO),
+        fixtures.tone("b.wav", 2400, Layout::MONO),
+    ];
+
+    assert_eq!(render(&paths, Layout::STEREO, RATE), 7200);
+    assert_eq!(render(&paths, Layout::SURROUND_5_1, RATE), 7200);
+
+    // Resampling may round each entry by a frame or so.
+    let frames = render(&paths, Layout::STEREO, 44100);
+    assert!(frames.abs_diff(6615) <= 4, "{frames}");
+}
+
+#[test]
+fn skips_invalid_entries() {
+    let fixtures = Fixtures::new("skip");
+    let paths = [
+        fixtures.tone("a.wav", 4800, Layout::STEREO),
+        fixtures.garbage("b.wav"),
+        fixtures.tone("c.wav", 2400, Layout::STEREO),
+    ];
+
+    assert_eq!(render(&paths, Layout::STEREO, RATE), 7200);
+}
+
+#[test]
+fn renders_past_run_of_invalid_entries() {
+    let fixtures = Fixtures::new("run");
+    let paths = [
+        fixtures.garbage("cover.jpg"),
+        fixtures.garbage("folder.jpg"),
+        fixtures.garbage("album.cue"),
+        fixtures.garbage("rip.log"),
+        fixtures.tone("a.wav", 4800, Layout::STEREO),
+        fixtures.garbage("notes.txt"),
+    ];
+
+    assert_eq!(render(&paths, Layout::STEREO, RATE), 4800);
+}
+
+#[test]
+fn retries_unreadable_entries() {
+    let fixtures = Fixtures::new("retry");
+    let missing = Entry::new(fixtures.dir.join("a.wav"), Quality::High).source();
+    let invalid = Entry::new(fixtures.garbage("b.wav"), Quality::High).source();
+
+    assert!(missing.probe().is_err());
+    assert!(invalid.probe().is_err());
+    assert_eq!(missing.is_valid(), None);
+    assert_eq!(invalid.is_valid(), Some(false));
+
+    // A file that could not be read before may well be readable now.
+    fixtures.tone("a.wav", 4800, Layout::STEREO);
+    assert!(missing.probe().is_ok());
+    assert_eq!(missing.is_valid(), Some(true));
+}
+
+#[test]
+fn seeks_within_sound() {
+    let fixtures = Fixtures::new("seek");
+    let path = fixtures.tone("a.wav", RATE as usize, Layout::STEREO);
+    let mut sound = open(&path);
+
+    assert_eq!(sound.duration(), Some(Duration::from_secs(1)));
+    assert!(sound.seek(SeekFrom::Fraction(0.5)));
+    assert!(sound.position().abs_diff(Duration::from_millis(500)) < Duration::from_millis(5));
+
+    assert!(sound.seek(SeekFrom::Backward(Duration::from_millis(250))));
+    assert!(sound.position().abs_diff(Duration::from_millis(250)) < Duration::from_millis(5));
+
+    assert!(sound.rewind());
+    assert_eq!(sound.position(), Duration::ZERO);
+
+    // There is nothing left to play at the very end.
+    assert!(!sound.seek(SeekFrom::Start(Duration::from_secs(5))));
+}